@@ -1,79 +1,562 @@
-fn part1(games: &Vec<String>) -> u32 {
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs, process,
+};
+
+use aoc_2023::escape::json_string;
+
+/// a single handful of cubes revealed from the bag, color -> count
+type Draw = BTreeMap<String, u32>;
+
+/// how many cubes of each color the bag holds. every color in the game log needs a limit
+type BagLimits = BTreeMap<String, u32>;
+
+const DEFAULT_LIMITS: &str = "red=12,green=13,blue=14";
+
+#[derive(Debug, Clone)]
+struct Game {
+    id: u32,
+    draws: Vec<Draw>,
+}
+
+impl Game {
+    /// the fewest cubes of each color that could have been in the bag for this game
+    fn min_required(&self) -> Draw {
+        let mut min_required = Draw::new();
+
+        for draw in &self.draws {
+            for (color, &count) in draw {
+                let required = min_required.entry(color.clone()).or_insert(0);
+                if count > *required {
+                    *required = count;
+                }
+            }
+        }
+
+        min_required
+    }
+}
+
+fn parse_draw(string: &str) -> Draw {
+    let mut draw = Draw::new();
+
+    for ball_count in string.split(',').map(|s| s.trim()) {
+        let (count, color) = ball_count
+            .split_once(' ')
+            .unwrap_or_else(|| panic!("Error parsing ball count: {}", ball_count));
+        let count = count
+            .parse::<u32>()
+            .unwrap_or_else(|_| panic!("Error parsing ball count: {}", ball_count));
+
+        *draw.entry(color.trim().to_string()).or_insert(0) += count;
+    }
+
+    draw
+}
+
+/// parses a line of the form `Game <id>: <draw>; <draw>; ...`
+fn parse_game(line: &str) -> Game {
+    let (header, set_data) = line
+        .split_once(':')
+        .unwrap_or_else(|| panic!("Error parsing game: {}", line));
+
+    let id = header
+        .trim()
+        .strip_prefix("Game")
+        .and_then(|id| id.trim().parse::<u32>().ok())
+        .unwrap_or_else(|| panic!("Error parsing game id: {}", header));
+
+    let draws = set_data
+        .split(';')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(parse_draw)
+        .collect();
+
+    Game { id, draws }
+}
+
+fn parse_games(lines: &[String]) -> Vec<Game> {
+    lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| parse_game(line))
+        .collect()
+}
+
+/// every color that shows up in at least one draw
+fn colors_in(games: &[Game]) -> BTreeSet<String> {
     games
         .iter()
-        .enumerate()
-        .map(|(idx, game)| {
-            let game_id = idx + 1;
-            let set_data = &game[game.find(":").unwrap() + 1..];
-            let sets = set_data.split(";").map(|s| s.trim());
-
-            for set in sets {
-                let ball_counts = set.split(",").map(|s| s.trim());
-
-                for ball_count in ball_counts {
-                    let ball_count_split = ball_count.split(" ").collect::<Vec<&str>>();
-                    let count = ball_count_split[0].parse::<u32>().unwrap();
-                    let color = ball_count_split[1];
-
-                    if count
-                        > match color {
-                            "red" => 12,
-                            "green" => 13,
-                            "blue" => 14,
-                            _ => panic!("Error parsing ball color: {}", color),
-                        }
-                    {
-                        return 0;
-                    }
+        .flat_map(|game| game.draws.iter())
+        .flat_map(|draw| draw.keys().cloned())
+        .collect()
+}
+
+/// parses limits of the form `red=12,green=13`. newlines are accepted as separators too, so the
+/// same format works for a config file with one limit per line
+fn parse_limits(spec: &str) -> Result<BagLimits, String> {
+    let mut limits = BagLimits::new();
+
+    for entry in spec
+        .split([',', '\n'])
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty() && !s.starts_with('#'))
+    {
+        let (color, count) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("invalid limit `{}`, expected <color>=<count>", entry))?;
+        let count = count
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("invalid count in limit `{}`", entry))?;
+
+        limits.insert(color.trim().to_string(), count);
+    }
+
+    Ok(limits)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReportFormat {
+    Table,
+    Json,
+}
+
+#[derive(Debug)]
+struct Options {
+    limits: BagLimits,
+    report: Option<ReportFormat>,
+    solve: bool,
+    target: BTreeSet<u32>,
+    budget: Option<u32>,
+}
+
+/// reads `--limit <spec>`, `--limit-file <path>`, `--report <table|json>` and the solver options
+/// `--solve`, `--target <id,id,...>`, `--budget <cubes>` from the command line. limits given are
+/// merged over the default ones
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut limits = parse_limits(DEFAULT_LIMITS)?;
+    let mut report = None;
+    let mut solve = false;
+    let mut target = BTreeSet::new();
+    let mut budget = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--limit" => {
+                let spec = args.next().ok_or("--limit requires a value")?;
+                limits.extend(parse_limits(spec)?);
+            }
+            "--limit-file" => {
+                let path = args.next().ok_or("--limit-file requires a path")?;
+                let spec = fs::read_to_string(path)
+                    .map_err(|e| format!("could not read {}: {}", path, e))?;
+                limits.extend(parse_limits(&spec)?);
+            }
+            "--report" => {
+                report = Some(match args.next().map(|s| s.as_str()) {
+                    Some("table") => ReportFormat::Table,
+                    Some("json") => ReportFormat::Json,
+                    Some(other) => return Err(format!("unknown report format `{}`", other)),
+                    None => return Err("--report requires a format (table or json)".to_string()),
+                });
+            }
+            "--solve" => solve = true,
+            "--target" => {
+                let ids = args.next().ok_or("--target requires a list of game ids")?;
+                for id in ids.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+                    target.insert(
+                        id.parse::<u32>()
+                            .map_err(|_| format!("invalid game id `{}` in --target", id))?,
+                    );
                 }
             }
+            "--budget" => {
+                let cubes = args.next().ok_or("--budget requires a cube count")?;
+                budget = Some(
+                    cubes
+                        .parse::<u32>()
+                        .map_err(|_| format!("invalid budget `{}`", cubes))?,
+                );
+            }
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        }
+    }
 
-            game_id as u32
-        })
-        .sum()
+    Ok(Options {
+        limits,
+        report,
+        solve,
+        target,
+        budget,
+    })
+}
+
+/// a draw that pulled more cubes of one color than the bag holds
+#[derive(Debug, Clone)]
+struct Violation {
+    /// 1-based position of the draw within its game
+    draw_no: usize,
+    color: String,
+    count: u32,
+    limit: u32,
+}
+
+/// returns the first draw (and within it, the first color alphabetically) that exceeds the limits.
+/// errors on a color the limits say nothing about
+fn first_violation(game: &Game, limits: &BagLimits) -> Result<Option<Violation>, String> {
+    for (idx, draw) in game.draws.iter().enumerate() {
+        for (color, &count) in draw {
+            let limit = *limits
+                .get(color)
+                .ok_or_else(|| format!("no limit given for {}", color))?;
+            if count > limit {
+                return Ok(Some(Violation {
+                    draw_no: idx + 1,
+                    color: color.clone(),
+                    count,
+                    limit,
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn is_possible(game: &Game, limits: &BagLimits) -> Result<bool, String> {
+    Ok(first_violation(game, limits)?.is_none())
+}
+
+/// product of the minimum cube counts over every color seen in the game log
+fn power(min_required: &Draw, colors: &BTreeSet<String>) -> u64 {
+    colors
+        .iter()
+        .map(|color| min_required.get(color).copied().unwrap_or(0) as u64)
+        .product()
+}
+
+fn part1(games: &[Game], limits: &BagLimits) -> Result<u32, String> {
+    let mut total = 0;
+    for game in games {
+        if is_possible(game, limits)? {
+            total += game.id;
+        }
+    }
+    Ok(total)
 }
 
-fn part2(games: &Vec<String>) -> u32 {
+fn part2(games: &[Game]) -> u64 {
+    let colors = colors_in(games);
+
     games
         .iter()
-        .enumerate()
-        .map(|(_, game)| {
-            let set_data = &game[game.find(":").unwrap() + 1..];
-            let sets = set_data.split(";").map(|s| s.trim());
-
-            let mut min_required = vec![0, 0, 0]; // R, G, B
-
-            for set in sets {
-                let ball_counts = set.split(",").map(|s| s.trim());
-
-                for ball_count in ball_counts {
-                    let ball_count_split = ball_count.split(" ").collect::<Vec<&str>>();
-                    let count = ball_count_split[0].parse::<u32>().unwrap();
-                    let color = ball_count_split[1];
-
-                    let clr_idx = match color {
-                        "red" => 0,
-                        "green" => 1,
-                        "blue" => 2,
-                        _ => panic!("Error parsing ball color: {}", color),
-                    };
-
-                    if count > min_required[clr_idx] {
-                        min_required[clr_idx] = count;
-                    }
-                }
+        .map(|game| power(&game.min_required(), &colors))
+        .sum()
+}
+
+fn format_cube_set(cubes: &Draw) -> String {
+    cubes
+        .iter()
+        .map(|(color, count)| format!("{} {}", count, color))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// per game: whether it is possible under the limits, why not if it isn't, and its minimum cube set
+fn report(games: &[Game], limits: &BagLimits, format: ReportFormat) -> Result<String, String> {
+    let colors = colors_in(games);
+    let mut lines = vec![];
+
+    if format == ReportFormat::Table {
+        lines.push(format!(
+            "{:>6}  {:<10}  {:<40}  {:<30}  {:>8}",
+            "game", "possible", "first violation", "minimum cube set", "power"
+        ));
+    }
+
+    for game in games {
+        let violation = first_violation(game, limits)?;
+        let possible = violation.is_none();
+        let min_required = game.min_required();
+        let power = power(&min_required, &colors);
+
+        match format {
+            ReportFormat::Table => {
+                let violation = match &violation {
+                    Some(v) => format!(
+                        "draw {}: {} {} > limit {}",
+                        v.draw_no, v.count, v.color, v.limit
+                    ),
+                    None => "-".to_string(),
+                };
+                lines.push(format!(
+                    "{:>6}  {:<10}  {:<40}  {:<30}  {:>8}",
+                    game.id,
+                    if possible { "yes" } else { "no" },
+                    violation,
+                    format_cube_set(&min_required),
+                    power
+                ));
+            }
+            ReportFormat::Json => {
+                let violation = match &violation {
+                    Some(v) => format!(
+                        "{{\"draw\": {}, \"color\": {}, \"count\": {}, \"limit\": {}}}",
+                        v.draw_no,
+                        json_string(&v.color),
+                        v.count,
+                        v.limit
+                    ),
+                    None => "null".to_string(),
+                };
+                let min_required = min_required
+                    .iter()
+                    .map(|(color, count)| format!("{}: {}", json_string(color), count))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                lines.push(format!(
+                    "  {{\"id\": {}, \"possible\": {}, \"violation\": {}, \"min_required\": {{{}}}, \"power\": {}}}",
+                    game.id,
+                    possible,
+                    violation,
+                    min_required,
+                    power
+                ));
+            }
+        }
+    }
+
+    Ok(match format {
+        ReportFormat::Table => lines.join("\n"),
+        ReportFormat::Json => format!("[\n{}\n]", lines.join(",\n")),
+    })
+}
+
+/// the smallest bag that makes every given game possible: the per-color max of their minimum sets
+fn tightest_bag<'a>(games: impl IntoIterator<Item = &'a Game>) -> BagLimits {
+    let mut bag = BagLimits::new();
+
+    for game in games {
+        for (color, count) in game.min_required() {
+            let limit = bag.entry(color).or_insert(0);
+            if count > *limit {
+                *limit = count;
             }
-            min_required.iter().product::<u32>()
+        }
+    }
+
+    bag
+}
+
+/// a bag on the pareto frontier: no other bag within budget keeps as many games possible with
+/// fewer cubes
+#[derive(Debug, Clone)]
+struct FrontierPoint {
+    bag: BagLimits,
+    total_cubes: u32,
+    possible_ids: Vec<u32>,
+}
+
+/// finds the pareto frontier of (total cubes, games possible) over all bags that keep every game
+/// in `target` possible and hold at most `budget` cubes.
+///
+/// only per-color limits equal to some game's minimum requirement need to be considered, since any
+/// limit in between two of those makes exactly the same games possible as the lower one.
+fn pareto_frontier(
+    games: &[Game],
+    target: &BTreeSet<u32>,
+    budget: u32,
+) -> Result<Vec<FrontierPoint>, String> {
+    if let Some(id) = target.iter().find(|&&id| !games.iter().any(|g| g.id == id)) {
+        return Err(format!("game {} is not in the game log", id));
+    }
+
+    let colors = colors_in(games).into_iter().collect::<Vec<String>>();
+    let base = tightest_bag(games.iter().filter(|g| target.contains(&g.id)));
+    let min_required = games
+        .iter()
+        .map(|game| (game.id, game.min_required()))
+        .collect::<Vec<(u32, Draw)>>();
+
+    // candidate limits for each color, ascending, never below what the target games need
+    let candidates = colors
+        .iter()
+        .map(|color| {
+            let floor = base.get(color).copied().unwrap_or(0);
+            let mut values = min_required
+                .iter()
+                .map(|(_, required)| required.get(color).copied().unwrap_or(0))
+                .filter(|&count| count >= floor)
+                .collect::<BTreeSet<u32>>();
+            values.insert(floor);
+            values.into_iter().collect::<Vec<u32>>()
         })
-        .sum()
+        .collect::<Vec<Vec<u32>>>();
+
+    let floor_total = candidates.iter().map(|values| values[0]).sum::<u32>();
+    if floor_total > budget {
+        return Err(format!(
+            "the target games need at least {} cubes, which is over the budget of {}",
+            floor_total, budget
+        ));
+    }
+
+    // enumerate every combination of candidate limits that fits in the budget
+    let mut bags: Vec<(u32, Vec<u32>)> = vec![];
+    let mut stack: Vec<(Vec<u32>, u32)> = vec![(vec![], 0)];
+
+    while let Some((chosen, total)) = stack.pop() {
+        let color_idx = chosen.len();
+        if color_idx == colors.len() {
+            bags.push((total, chosen));
+            continue;
+        }
+
+        // cubes still needed by the colors after this one at their smallest
+        let rest = candidates[color_idx + 1..]
+            .iter()
+            .map(|values| values[0])
+            .sum::<u32>();
+
+        for &value in &candidates[color_idx] {
+            if total + value + rest > budget {
+                break;
+            }
+            let mut next = chosen.clone();
+            next.push(value);
+            stack.push((next, total + value));
+        }
+    }
+
+    let mut points = bags
+        .into_iter()
+        .map(|(total_cubes, limits)| {
+            let bag = colors.iter().cloned().zip(limits).collect::<BagLimits>();
+            let possible_ids = min_required
+                .iter()
+                .filter(|(_, required)| {
+                    required
+                        .iter()
+                        .all(|(color, &count)| count <= bag.get(color).copied().unwrap_or(0))
+                })
+                .map(|(id, _)| *id)
+                .collect::<Vec<u32>>();
+
+            FrontierPoint {
+                bag,
+                total_cubes,
+                possible_ids,
+            }
+        })
+        .collect::<Vec<FrontierPoint>>();
+
+    // cheapest first, and for equal cost the one keeping the most games possible
+    points.sort_by(|a, b| {
+        a.total_cubes
+            .cmp(&b.total_cubes)
+            .then(b.possible_ids.len().cmp(&a.possible_ids.len()))
+            .then(a.bag.cmp(&b.bag))
+    });
+
+    let mut frontier: Vec<FrontierPoint> = vec![];
+    for point in points {
+        if frontier
+            .last()
+            .is_none_or(|best| point.possible_ids.len() > best.possible_ids.len())
+        {
+            frontier.push(point);
+        }
+    }
+
+    Ok(frontier)
+}
+
+fn format_ids(ids: &[u32]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn solve(games: &[Game], target: &BTreeSet<u32>, budget: Option<u32>) -> Result<String, String> {
+    let tightest = tightest_bag(games);
+    let tightest_total = tightest.values().sum::<u32>();
+
+    let mut lines = vec![format!(
+        "tightest bag for all games: {} ({} cubes)",
+        format_cube_set(&tightest),
+        tightest_total
+    )];
+
+    let budget = budget.unwrap_or(tightest_total);
+    let frontier = pareto_frontier(games, target, budget)?;
+
+    lines.push(format!(
+        "pareto frontier keeping games [{}] possible within {} cubes:",
+        format_ids(&target.iter().copied().collect::<Vec<u32>>()),
+        budget
+    ));
+    lines.push(format!(
+        "{:>6}  {:>8}  {:<30}  possible games",
+        "cubes", "possible", "bag"
+    ));
+    for point in frontier {
+        lines.push(format!(
+            "{:>6}  {:>8}  {:<30}  {}",
+            point.total_cubes,
+            point.possible_ids.len(),
+            format_cube_set(&point.bag),
+            format_ids(&point.possible_ids)
+        ));
+    }
+
+    Ok(lines.join("\n"))
 }
 
 pub fn main() {
-    let games = include_bytes!("sample_input_data/day2.txt")
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    });
+
+    let lines = include_bytes!("sample_input_data/day2.txt")
         .split(|c| c == &b'\n')
         .map(|line| String::from_utf8(line.to_vec()).unwrap())
         .collect::<Vec<String>>();
 
-    println!("part 1: {}", part1(&games));
+    let games = parse_games(&lines);
+
+    if options.solve {
+        match solve(&games, &options.target, options.budget) {
+            Ok(output) => println!("{}", output),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(format) = options.report {
+        match report(&games, &options.limits, format) {
+            Ok(output) => println!("{}", output),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    match part1(&games, &options.limits) {
+        Ok(total) => println!("part 1: {}", total),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
     println!("part 2: {}", part2(&games));
 }
@@ -1,188 +1,577 @@
-use std::cmp;
+use std::{collections::HashMap, env, fs, process};
 
 #[derive(Debug, Clone)]
 struct NumSearchResult {
     value: u32,
+    line_idx: usize,
     start_idx: usize,
     end_idx: usize,
 }
 
-/// returns a list of indices of gears
-fn parse_gears(string: &str) -> Vec<usize> {
-    let mut gear_indices: Vec<usize> = Vec::new();
-    for (idx, chr) in string.char_indices() {
-        if chr == '*' {
-            gear_indices.push(idx);
+#[derive(Debug, Clone)]
+struct Symbol {
+    chr: char,
+    line_idx: usize,
+    idx: usize,
+}
+
+/// the engine schematic, with the adjacency between numbers and symbols worked out once.
+///
+/// the adjacency is a bipartite graph: `number_symbols[n]` lists the indices (into `symbols`) of
+/// every symbol touching number `n`, and `symbol_numbers[s]` lists the numbers touching symbol `s`.
+///
+/// lines don't need to be the same length: any cell past the end of a line (or above the first /
+/// below the last line) is treated as empty, so ragged, tall and wide schematics all work the same
+#[derive(Debug)]
+struct Schematic {
+    numbers: Vec<NumSearchResult>,
+    symbols: Vec<Symbol>,
+    number_symbols: Vec<Vec<usize>>,
+    symbol_numbers: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NeighbourCount {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Combine {
+    Product,
+    Sum,
+    Max,
+}
+
+/// what makes a symbol a gear, and how its ratio is worked out from the numbers around it
+#[derive(Debug, Clone)]
+struct GearRule {
+    symbols: Vec<char>,
+    neighbours: NeighbourCount,
+    combine: Combine,
+}
+
+impl Default for GearRule {
+    /// the puzzle's rule: a '*' next to exactly two part numbers, whose ratio is their product
+    fn default() -> Self {
+        GearRule {
+            symbols: vec!['*'],
+            neighbours: NeighbourCount::Exactly(2),
+            combine: Combine::Product,
+        }
+    }
+}
+
+impl GearRule {
+    fn is_gear(&self, symbol: &Symbol, adjacent_numbers: usize) -> bool {
+        self.symbols.contains(&symbol.chr)
+            && match self.neighbours {
+                NeighbourCount::Exactly(n) => adjacent_numbers == n,
+                NeighbourCount::AtLeast(n) => adjacent_numbers >= n,
+            }
+    }
+
+    fn ratio(&self, numbers: &[&NumSearchResult]) -> u64 {
+        let values = numbers.iter().map(|number| number.value as u64);
+        match self.combine {
+            Combine::Product => values.product(),
+            Combine::Sum => values.sum(),
+            Combine::Max => values.max().unwrap_or(0),
         }
     }
-    gear_indices
 }
 
-fn string_has_symbol(string: &str) -> bool {
-    // check if string has any character that is neither a digit, nor a '.' (period)
-    string
-        .chars()
-        .any(|c| !(c.is_ascii_digit() || c == char::from(b'.')))
+fn is_symbol(chr: char) -> bool {
+    // any character that is neither a digit, nor a '.' (period)
+    !(chr.is_ascii_digit() || chr == '.')
+}
+
+/// splits the schematic into a grid of chars. columns are char positions, not byte offsets, so
+/// non-ASCII symbols (like '§') are supported and take up exactly one column. characters that
+/// can't be placed on the grid unambiguously are rejected: non-ASCII digits (which could be
+/// mistaken for part of a number) and control characters
+fn parse_grid(schematic_lines: &[String]) -> Result<Vec<Vec<char>>, String> {
+    schematic_lines
+        .iter()
+        .enumerate()
+        .map(|(line_idx, line)| {
+            let row = line.chars().collect::<Vec<char>>();
+            match row
+                .iter()
+                .position(|&chr| chr.is_control() || (chr.is_numeric() && !chr.is_ascii_digit()))
+            {
+                Some(idx) => Err(format!(
+                    "unsupported character {:?} at line {}, column {}",
+                    row[idx],
+                    line_idx + 1,
+                    idx + 1
+                )),
+                None => Ok(row),
+            }
+        })
+        .collect()
 }
 
-/// parse integers in string
-fn parse_numbers(string: &String) -> Vec<NumSearchResult> {
-    let chars = string.chars().collect::<Vec<char>>();
+/// returns all symbols in a row
+fn parse_symbols(row: &[char], line_idx: usize) -> Vec<Symbol> {
+    row.iter()
+        .enumerate()
+        .filter(|&(_, &chr)| is_symbol(chr))
+        .map(|(idx, &chr)| Symbol { chr, line_idx, idx })
+        .collect()
+}
 
+/// parse integers in a row
+fn parse_numbers(row: &[char], line_idx: usize) -> Result<Vec<NumSearchResult>, String> {
     let mut results: Vec<NumSearchResult> = Vec::new();
 
     let mut start_pos = 0;
-    let mut end_pos = 0;
 
-    while end_pos < string.len() {
-        if chars[start_pos].is_ascii_digit() {
-            end_pos = start_pos + 1;
+    while start_pos < row.len() {
+        if row[start_pos].is_ascii_digit() {
+            let mut end_pos = start_pos;
+            let mut value: u32 = 0;
 
-            while end_pos < string.len() && chars[end_pos].is_ascii_digit() {
+            while end_pos < row.len() && row[end_pos].is_ascii_digit() {
+                value = value
+                    .checked_mul(10)
+                    .and_then(|v| v.checked_add(row[end_pos].to_digit(10).unwrap()))
+                    .ok_or_else(|| {
+                        format!(
+                            "number at line {}, column {} is too large",
+                            line_idx + 1,
+                            start_pos + 1
+                        )
+                    })?;
                 end_pos += 1;
             }
 
             results.push(NumSearchResult {
-                value: string[start_pos..end_pos].parse::<u32>().unwrap(),
+                value,
+                line_idx,
                 start_idx: start_pos,
                 end_idx: end_pos - 1,
             });
             start_pos = end_pos
         } else {
             start_pos += 1;
-            end_pos += 1;
         }
     }
 
-    results
+    Ok(results)
 }
 
-fn parse_part_nums(schematic_lines: &Vec<String>) -> Vec<Vec<NumSearchResult>> {
-    schematic_lines
-        .iter()
-        .enumerate()
-        .map(|(idx, schematic_line)| {
-            parse_numbers(&schematic_line)
-                .iter()
-                .filter(|&part_num| {
-                    let start_index = cmp::max(part_num.start_idx as i32 - 1, 0) as usize;
-                    let end_index = cmp::min(part_num.end_idx + 2, schematic_line.len());
+impl Schematic {
+    fn parse(schematic_lines: &[String]) -> Result<Schematic, String> {
+        let grid = parse_grid(schematic_lines)?;
 
-                    if idx > 0 {
-                        // there is a line above
-                        let line_above = &schematic_lines[idx - 1];
+        let mut numbers = Vec::new();
+        for (line_idx, row) in grid.iter().enumerate() {
+            numbers.extend(parse_numbers(row, line_idx)?);
+        }
 
-                        if string_has_symbol(&line_above[start_index..end_index]) {
-                            return true;
-                        }
-                    }
+        let symbols = grid
+            .iter()
+            .enumerate()
+            .flat_map(|(line_idx, row)| parse_symbols(row, line_idx))
+            .collect::<Vec<Symbol>>();
 
-                    if idx < schematic_lines.len() - 1 {
-                        // if there is a line  below
-                        let line_below = &schematic_lines[idx + 1];
+        let symbol_at = symbols
+            .iter()
+            .enumerate()
+            .map(|(symbol_idx, symbol)| ((symbol.line_idx, symbol.idx), symbol_idx))
+            .collect::<HashMap<(usize, usize), usize>>();
 
-                        if string_has_symbol(&line_below[start_index..end_index]) {
-                            return true;
-                        }
-                    }
+        let mut number_symbols = vec![vec![]; numbers.len()];
+        let mut symbol_numbers = vec![vec![]; symbols.len()];
 
-                    if string_has_symbol(&schematic_line[start_index..start_index + 1])
-                        || string_has_symbol(&schematic_line[end_index - 1..end_index])
-                    {
-                        return true;
+        for (number_idx, number) in numbers.iter().enumerate() {
+            // every cell in the box around the number, including the number itself (which can't
+            // hold a symbol, so it doesn't matter)
+            for line_idx in number.line_idx.saturating_sub(1)..=number.line_idx + 1 {
+                for idx in number.start_idx.saturating_sub(1)..=number.end_idx + 1 {
+                    if let Some(&symbol_idx) = symbol_at.get(&(line_idx, idx)) {
+                        number_symbols[number_idx].push(symbol_idx);
+                        symbol_numbers[symbol_idx].push(number_idx);
                     }
-                    false
-                })
-                .map(|res| res.clone())
-                .collect()
+                }
+            }
+        }
+
+        Ok(Schematic {
+            numbers,
+            symbols,
+            number_symbols,
+            symbol_numbers,
         })
-        .collect::<Vec<Vec<NumSearchResult>>>()
+    }
+
+    /// numbers adjacent to at least one symbol
+    fn part_numbers(&self) -> impl Iterator<Item = &NumSearchResult> {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(number, _)| number)
+    }
+
+    /// numbers adjacent to each symbol, for the symbols matching `predicate`
+    fn numbers_around<'a>(
+        &'a self,
+        predicate: impl Fn(&Symbol) -> bool + 'a,
+    ) -> impl Iterator<Item = (&'a Symbol, Vec<&'a NumSearchResult>)> + 'a {
+        self.symbols
+            .iter()
+            .zip(&self.symbol_numbers)
+            .filter(move |(symbol, _)| predicate(symbol))
+            .map(|(symbol, numbers)| (symbol, numbers.iter().map(|&n| &self.numbers[n]).collect()))
+    }
+
+    /// every symbol that counts as a gear under `rule`, with all the numbers around it
+    fn gears<'a>(
+        &'a self,
+        rule: &'a GearRule,
+    ) -> impl Iterator<Item = (&'a Symbol, Vec<&'a NumSearchResult>)> + 'a {
+        self.numbers_around(|symbol| rule.symbols.contains(&symbol.chr))
+            .filter(|(symbol, numbers)| rule.is_gear(symbol, numbers.len()))
+    }
+
+    /// sum of the numbers adjacent to at least one `chr` symbol
+    fn sum_adjacent_to(&self, chr: char) -> u64 {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, symbols)| symbols.iter().any(|&s| self.symbols[s].chr == chr))
+            .map(|(number, _)| number.value as u64)
+            .sum()
+    }
 }
 
-fn part1(schematic_lines: &Vec<String>) -> u64 {
-    parse_part_nums(schematic_lines)
-        .iter()
-        .flat_map(|line_nums| line_nums.iter())
-        .map(|res| res.value as u64)
+fn part1(schematic: &Schematic) -> u64 {
+    schematic
+        .part_numbers()
+        .map(|number| number.value as u64)
         .sum()
 }
 
-fn part2(schematic_lines: &Vec<String>) -> u64 {
-    let part_nums_line_wise = parse_part_nums(schematic_lines);
+fn part2(schematic: &Schematic, rule: &GearRule) -> u64 {
+    schematic
+        .gears(rule)
+        .map(|(_, numbers)| rule.ratio(&numbers))
+        .sum()
+}
 
-    let mut gear_ratio_sum: u64 = 0;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Highlight {
+    PartNumber,
+    OtherNumber,
+    Gear,
+}
 
-    for (idx, schematic_line) in schematic_lines.iter().enumerate() {
-        // find potential gears in each line
-        let potential_gear_indices = parse_gears(&schematic_line);
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RenderMode {
+    Ansi,
+    Plain,
+    Html,
+}
 
-        for potential_gear_index in potential_gear_indices {
-            // for each potential gear, check for adjacent part numbers
-            let mut adjacent_part_nums: Vec<u32> = Vec::new();
+/// works out how each cell of the schematic should be highlighted, from the positions of the
+/// numbers and gears. cells that aren't in the map are printed as they are
+fn highlights(schematic: &Schematic, rule: &GearRule) -> HashMap<(usize, usize), Highlight> {
+    let mut highlights = HashMap::new();
 
-            if idx > 0 {
-                // there is a line above
-                let part_nums_above = &part_nums_line_wise[idx - 1];
+    for (number, symbols) in schematic.numbers.iter().zip(&schematic.number_symbols) {
+        let highlight = if symbols.is_empty() {
+            Highlight::OtherNumber
+        } else {
+            Highlight::PartNumber
+        };
+        for idx in number.start_idx..=number.end_idx {
+            highlights.insert((number.line_idx, idx), highlight);
+        }
+    }
 
-                for part_num in part_nums_above {
-                    if adjacent_part_nums.len() >= 2 {
-                        break;
-                    }
-                    if (part_num.start_idx as i32 - 1..part_num.end_idx as i32 + 2)
-                        .contains(&(potential_gear_index as i32))
-                    {
-                        adjacent_part_nums.push(part_num.value);
-                    }
-                }
-            }
+    for (gear, _) in schematic.gears(rule) {
+        highlights.insert((gear.line_idx, gear.idx), Highlight::Gear);
+    }
+
+    highlights
+}
+
+fn html_escape(chr: char) -> String {
+    match chr {
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '&' => "&amp;".to_string(),
+        '"' => "&quot;".to_string(),
+        _ => chr.to_string(),
+    }
+}
+
+/// prints the schematic with part numbers, numbers that don't count and gears marked out.
+///
+/// `Ansi` colors them green, red and yellow. `Plain` keeps the schematic as is and adds a line
+/// under each row with `^` under part numbers, `x` under other numbers and `G` under gears.
+/// `Html` wraps them in `<span>`s inside a `<pre>`
+fn render(
+    schematic_lines: &[String],
+    schematic: &Schematic,
+    rule: &GearRule,
+    mode: RenderMode,
+) -> String {
+    let highlights = highlights(schematic, rule);
+    let mut output = String::new();
+
+    if mode == RenderMode::Html {
+        output.push_str(concat!(
+            "<style>\n",
+            ".part { color: green; }\n",
+            ".other { color: red; }\n",
+            ".gear { background: yellow; font-weight: bold; }\n",
+            "</style>\n",
+            "<pre>\n"
+        ));
+    }
 
-            if idx < schematic_line.len() - 1 {
-                // there is line below
+    for (line_idx, line) in schematic_lines.iter().enumerate() {
+        let mut markers = String::new();
 
-                let part_nums_below = &part_nums_line_wise[idx + 1];
+        for (idx, chr) in line.chars().enumerate() {
+            let highlight = highlights.get(&(line_idx, idx)).copied();
 
-                for part_num in part_nums_below {
-                    if adjacent_part_nums.len() >= 2 {
-                        break;
+            match mode {
+                RenderMode::Ansi => match highlight {
+                    Some(Highlight::PartNumber) => {
+                        output.push_str(&format!("\x1b[32m{}\x1b[0m", chr))
                     }
-                    if (part_num.start_idx as i32 - 1..part_num.end_idx as i32 + 2)
-                        .contains(&(potential_gear_index as i32))
-                    {
-                        adjacent_part_nums.push(part_num.value);
+                    Some(Highlight::OtherNumber) => {
+                        output.push_str(&format!("\x1b[31m{}\x1b[0m", chr))
                     }
+                    Some(Highlight::Gear) => {
+                        output.push_str(&format!("\x1b[1;30;43m{}\x1b[0m", chr))
+                    }
+                    None => output.push(chr),
+                },
+                RenderMode::Plain => {
+                    output.push(chr);
+                    markers.push(match highlight {
+                        Some(Highlight::PartNumber) => '^',
+                        Some(Highlight::OtherNumber) => 'x',
+                        Some(Highlight::Gear) => 'G',
+                        None => ' ',
+                    });
                 }
+                RenderMode::Html => match highlight {
+                    Some(highlight) => output.push_str(&format!(
+                        "<span class=\"{}\">{}</span>",
+                        match highlight {
+                            Highlight::PartNumber => "part",
+                            Highlight::OtherNumber => "other",
+                            Highlight::Gear => "gear",
+                        },
+                        html_escape(chr)
+                    )),
+                    None => output.push_str(&html_escape(chr)),
+                },
             }
+        }
+
+        output.push('\n');
+        if mode == RenderMode::Plain && !markers.trim_end().is_empty() {
+            output.push_str(markers.trim_end());
+            output.push('\n');
+        }
+    }
+
+    if mode == RenderMode::Html {
+        output.push_str("</pre>\n");
+    }
 
-            // adjacent part numbers on the same line as the gear
-            let part_nums_on_current_line = &part_nums_line_wise[idx];
-            for part_num in part_nums_on_current_line {
-                if adjacent_part_nums.len() >= 2 {
-                    break;
+    output
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    input: Option<String>,
+    adjacent_to: Option<char>,
+    gear_rule: GearRule,
+    render: Option<RenderMode>,
+}
+
+/// reads `--input <path>`, `--adjacent-to <symbol>` and the gear rule (`--gear-symbols <symbols>`,
+/// `--gear-neighbours <n | n+>`, `--gear-combine <product | sum | max>`) and
+/// `--render <ansi | plain | html>` from the command line
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                options.input = Some(args.next().ok_or("--input requires a path")?.clone());
+            }
+            "--adjacent-to" => {
+                let symbol = args.next().ok_or("--adjacent-to requires a symbol")?;
+                let mut chars = symbol.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(chr), None) if is_symbol(chr) => options.adjacent_to = Some(chr),
+                    _ => return Err(format!("`{}` is not a single symbol", symbol)),
                 }
-                if potential_gear_index as i32 == (part_num.start_idx as i32) - 1
-                    || potential_gear_index == part_num.end_idx + 1
-                {
-                    adjacent_part_nums.push(part_num.value);
+            }
+            "--gear-symbols" => {
+                let symbols = args.next().ok_or("--gear-symbols requires symbols")?;
+                if let Some(chr) = symbols.chars().find(|&chr| !is_symbol(chr)) {
+                    return Err(format!("`{}` is not a symbol", chr));
                 }
+                options.gear_rule.symbols = symbols.chars().collect();
             }
-
-            if adjacent_part_nums.len() >= 2 {
-                // if there are 2 adjacent part numbers, add their product to overall gear ratio sum
-                gear_ratio_sum += (adjacent_part_nums[0] * adjacent_part_nums[1]) as u64;
+            "--gear-neighbours" => {
+                let count = args.next().ok_or("--gear-neighbours requires a count")?;
+                let invalid = || format!("invalid neighbour count `{}`", count);
+                options.gear_rule.neighbours = match count.strip_suffix('+') {
+                    Some(n) => NeighbourCount::AtLeast(n.parse().map_err(|_| invalid())?),
+                    None => NeighbourCount::Exactly(count.parse().map_err(|_| invalid())?),
+                };
+            }
+            "--gear-combine" => {
+                options.gear_rule.combine = match args.next().map(|s| s.as_str()) {
+                    Some("product") => Combine::Product,
+                    Some("sum") => Combine::Sum,
+                    Some("max") => Combine::Max,
+                    Some(other) => return Err(format!("unknown combine operation `{}`", other)),
+                    None => return Err("--gear-combine requires an operation".to_string()),
+                };
             }
+            "--render" => {
+                options.render = Some(match args.next().map(|s| s.as_str()) {
+                    Some("ansi") => RenderMode::Ansi,
+                    Some("plain") => RenderMode::Plain,
+                    Some("html") => RenderMode::Html,
+                    Some(other) => return Err(format!("unknown render mode `{}`", other)),
+                    None => return Err("--render requires a mode".to_string()),
+                });
+            }
+            _ => return Err(format!("unknown argument `{}`", arg)),
         }
     }
 
-    gear_ratio_sum
+    Ok(options)
 }
 
 pub fn main() {
-    let schematic_lines = String::from_utf8(include_bytes!("sample_input_data/day3.txt").to_vec())
-        .unwrap()
-        .split("\n")
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    });
+
+    let input = match &options.input {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("error: could not read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => String::from_utf8(include_bytes!("sample_input_data/day3.txt").to_vec()).unwrap(),
+    };
+
+    // `lines` also drops the '\r' of CRLF line endings, which would otherwise count as a symbol
+    let schematic_lines = input
+        .lines()
         .map(|s| s.to_string())
         .collect::<Vec<String>>();
 
-    println!("Part 1: {}", part1(&schematic_lines));
-    println!("Part 2: {}", part2(&schematic_lines));
+    let schematic = Schematic::parse(&schematic_lines).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+
+    if let Some(mode) = options.render {
+        print!(
+            "{}",
+            render(&schematic_lines, &schematic, &options.gear_rule, mode)
+        );
+        return;
+    }
+
+    if let Some(chr) = options.adjacent_to {
+        println!(
+            "Sum of numbers adjacent to '{}': {}",
+            chr,
+            schematic.sum_adjacent_to(chr)
+        );
+        return;
+    }
+
+    println!("Part 1: {}", part1(&schematic));
+    println!("Part 2: {}", part2(&schematic, &options.gear_rule));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(input: &str) -> (u64, u64) {
+        let lines = input
+            .lines()
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>();
+        let schematic = Schematic::parse(&lines).unwrap();
+        (part1(&schematic), part2(&schematic, &GearRule::default()))
+    }
+
+    #[test]
+    fn sample() {
+        assert_eq!(
+            solve(include_str!("sample_input_data/day3.txt")),
+            (4361, 467835)
+        );
+    }
+
+    #[test]
+    fn tall_grid() {
+        assert_eq!(
+            solve(include_str!("sample_input_data/day3_tall.txt")),
+            (24, 36)
+        );
+    }
+
+    #[test]
+    fn wide_grid() {
+        assert_eq!(
+            solve(include_str!("sample_input_data/day3_wide.txt")),
+            (482, 36)
+        );
+    }
+
+    #[test]
+    fn ragged_grid() {
+        assert_eq!(
+            solve(include_str!("sample_input_data/day3_ragged.txt")),
+            (1752, 16345)
+        );
+    }
+
+    #[test]
+    fn non_ascii_symbols_take_one_column() {
+        assert_eq!(solve("€12..34\n..*...§\n5.6...."), (52, 72));
+    }
+
+    #[test]
+    fn rejects_non_ascii_digits_and_control_characters() {
+        let parse = |input: &str| {
+            let lines = input
+                .lines()
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>();
+            Schematic::parse(&lines).map(|_| ())
+        };
+
+        assert_eq!(
+            parse("12\u{663}4"),
+            Err("unsupported character '\u{663}' at line 1, column 3".to_string())
+        );
+        assert_eq!(
+            parse("..*.\n1.\t4"),
+            Err("unsupported character '\\t' at line 2, column 3".to_string())
+        );
+    }
 }
@@ -1,64 +1,491 @@
-fn count_winning_numbers(card_string: &String) -> u32 {
-    let number_part = card_string.split(":").nth(1).unwrap().trim();
-    let number_part_split = number_part
-        .split("|")
-        .map(|part| part.trim())
-        .collect::<Vec<&str>>();
-
-    let winning_numbers = number_part_split[0]
-        .split_whitespace()
-        .map(|num_str| num_str.trim().parse::<u32>().unwrap())
-        .collect::<Vec<u32>>();
+use std::{collections::HashSet, env, fs, process};
+
+/// what happens when a card wins copies of cards past the end of the table
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Overflow {
+    /// cards past the end are never won
+    Clamp,
+    /// counting carries on from the first card
+    Wrap,
+    /// the table is invalid
+    Error,
+}
+
+#[derive(Debug, Clone)]
+struct Card {
+    id: u32,
+    winning: HashSet<u32>,
+    have: Vec<u32>,
+}
+
+impl Card {
+    /// how many of the numbers we have are winning numbers
+    fn matches(&self) -> usize {
+        self.have
+            .iter()
+            .filter(|num| self.winning.contains(num))
+            .count()
+    }
+}
 
-    let numbers_possessed = number_part_split[1]
+fn parse_numbers(string: &str) -> Result<Vec<u32>, String> {
+    string
         .split_whitespace()
-        .map(|num_str| num_str.trim().parse::<u32>().unwrap())
-        .collect::<Vec<u32>>();
+        .map(|num_str| {
+            num_str
+                .parse::<u32>()
+                .map_err(|_| format!("invalid number `{}`", num_str))
+        })
+        .collect()
+}
+
+/// parses a line of the form `Card <id>: <winning numbers> | <numbers we have>`
+fn parse_card(card_string: &str) -> Result<Card, String> {
+    let (header, number_part) = card_string
+        .split_once(':')
+        .ok_or_else(|| format!("expected `Card <id>: ...`, found `{}`", card_string))?;
 
-    numbers_possessed
+    let id = header
+        .trim()
+        .strip_prefix("Card")
+        .and_then(|id| id.trim().parse::<u32>().ok())
+        .ok_or_else(|| format!("invalid card id `{}`", header.trim()))?;
+
+    let (winning, have) = number_part
+        .split_once('|')
+        .ok_or_else(|| format!("card {}: expected `<winning numbers> | <numbers>`", id))?;
+
+    Ok(Card {
+        id,
+        winning: parse_numbers(winning)
+            .map_err(|e| format!("card {}: {}", id, e))?
+            .into_iter()
+            .collect(),
+        have: parse_numbers(have).map_err(|e| format!("card {}: {}", id, e))?,
+    })
+}
+
+/// parses every card in the table. copies are won by position ("the next N cards"), so the ids
+/// have to run consecutively for positions and ids to agree
+fn parse_cards(card_lines: &[String]) -> Result<Vec<Card>, String> {
+    let cards = card_lines
         .iter()
-        .filter(|num| winning_numbers.contains(num))
-        .count() as u32
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| parse_card(line).map_err(|e| format!("line {}: {}", idx + 1, e)))
+        .collect::<Result<Vec<Card>, String>>()?;
+
+    for pair in cards.windows(2) {
+        if pair[1].id != pair[0].id + 1 {
+            return Err(format!(
+                "card ids must be consecutive, found card {} after card {}",
+                pair[1].id, pair[0].id
+            ));
+        }
+    }
+
+    Ok(cards)
+}
+
+fn part1(cards: &[Card]) -> Result<u64, String> {
+    cards.iter().try_fold(0_u64, |total, card| {
+        let num_winning_numbers_possessed = card.matches() as u32;
+        let points = if num_winning_numbers_possessed > 0 {
+            2_u64
+                .checked_pow(num_winning_numbers_possessed - 1)
+                .ok_or_else(|| format!("card {} is worth too many points", card.id))?
+        } else {
+            0
+        };
+        total
+            .checked_add(points)
+            .ok_or_else(|| "too many points in total".to_string())
+    })
 }
 
-fn part1(card_lines: &Vec<String>) -> u64 {
-    card_lines
+/// the positions of the cards each card wins a copy of
+fn won_cards(cards: &[Card], overflow: Overflow) -> Result<Vec<Vec<usize>>, String> {
+    cards
         .iter()
-        .map(|card_line| {
-            let num_winning_numbers_possessed = count_winning_numbers(&card_line);
-            if num_winning_numbers_possessed > 0 {
-                2_u64.pow(num_winning_numbers_possessed - 1)
-            } else {
-                0
+        .enumerate()
+        .map(|(idx, card)| {
+            let last = idx + card.matches();
+            match overflow {
+                Overflow::Clamp => Ok((idx + 1..=last.min(cards.len() - 1)).collect()),
+                Overflow::Wrap => Ok((idx + 1..=last).map(|i| i % cards.len()).collect()),
+                Overflow::Error if last >= cards.len() => Err(format!(
+                    "card {} wins {} cards, but only {} come after it",
+                    card.id,
+                    card.matches(),
+                    cards.len() - idx - 1
+                )),
+                Overflow::Error => Ok((idx + 1..=last).collect()),
             }
         })
-        .sum()
+        .collect()
+}
+
+/// orders the cards so that every card comes after all the cards that win copies of it.
+///
+/// without wrapping that is just the table order. with wrapping a card can end up winning copies
+/// of itself further down the line, in which case the cascade never ends and this errors
+fn cascade_order(cards: &[Card], wins: &[Vec<usize>]) -> Result<Vec<usize>, String> {
+    let mut won_by = vec![0; cards.len()];
+    for won in wins {
+        for &idx in won {
+            won_by[idx] += 1;
+        }
+    }
+
+    let mut order = Vec::with_capacity(cards.len());
+    let mut ready = (0..cards.len())
+        .rev()
+        .filter(|&idx| won_by[idx] == 0)
+        .collect::<Vec<usize>>();
+
+    while let Some(idx) = ready.pop() {
+        order.push(idx);
+        for &won in wins[idx].iter().rev() {
+            won_by[won] -= 1;
+            if won_by[won] == 0 {
+                ready.push(won);
+            }
+        }
+    }
+
+    let Some(mut idx) = won_by.iter().position(|&count| count > 0) else {
+        return Ok(order);
+    };
+
+    // a card left over can just be downstream of a cycle. every left over card is won by another
+    // left over card though, so walking back along those eventually lands on one in the cycle
+    let mut seen = vec![false; cards.len()];
+    while !seen[idx] {
+        seen[idx] = true;
+        idx = (0..cards.len())
+            .find(|&from| won_by[from] > 0 && wins[from].contains(&idx))
+            .expect("a left over card is won by another left over card");
+    }
+
+    Err(format!(
+        "card {} keeps winning copies of itself, the cascade never ends",
+        cards[idx].id
+    ))
 }
 
-fn part2(card_lines: &Vec<String>) -> u64 {
-    let mut num_scratch_cards_of_id: Vec<u64> = vec![0; card_lines.len()];
+/// how many copies of each card we end up with, originals included
+fn count_copies(cards: &[Card], overflow: Overflow) -> Result<Vec<u64>, String> {
+    let wins = won_cards(cards, overflow)?;
+    let mut num_scratch_cards_of_id: Vec<u64> = vec![1; cards.len()];
 
-    for (idx, card_line) in card_lines.iter().enumerate() {
-        let num_winning_numbers = count_winning_numbers(&card_line);
+    for idx in cascade_order(cards, &wins)? {
+        let copies_of_this_card = num_scratch_cards_of_id[idx];
 
-        let copies_of_this_card = num_scratch_cards_of_id[idx] + 1;
-        num_scratch_cards_of_id[idx] += 1;
+        for &won in &wins[idx] {
+            num_scratch_cards_of_id[won] = num_scratch_cards_of_id[won]
+                .checked_add(copies_of_this_card)
+                .ok_or_else(|| format!("too many copies of card {}", cards[won].id))?;
+        }
+    }
 
-        for card_idx in idx + 1..idx + num_winning_numbers as usize + 1 {
-            num_scratch_cards_of_id[card_idx] += copies_of_this_card;
+    Ok(num_scratch_cards_of_id)
+}
+
+fn part2(cards: &[Card], overflow: Overflow) -> Result<u64, String> {
+    count_copies(cards, overflow)?
+        .iter()
+        .try_fold(0_u64, |total, &copies| total.checked_add(copies))
+        .ok_or_else(|| "too many scratch cards in total".to_string())
+}
+
+/// the full card-copy cascade, for explaining where the part 2 total comes from
+#[derive(Debug)]
+struct Simulation {
+    /// copies of each card, originals included
+    copies: Vec<u64>,
+    /// for each card, the cards it got copies from and how many copies each one gave it
+    won_from: Vec<Vec<(usize, u64)>>,
+    /// new copies per round: round 0 is the original cards, round n the copies won by round n - 1
+    rounds: Vec<u64>,
+    /// cards in play after each round
+    in_play: Vec<u64>,
+}
+
+fn simulate(cards: &[Card], overflow: Overflow) -> Result<Simulation, String> {
+    let wins = won_cards(cards, overflow)?;
+    let copies = count_copies(cards, overflow)?;
+
+    let mut won_from: Vec<Vec<(usize, u64)>> = vec![vec![]; cards.len()];
+    for (idx, won) in wins.iter().enumerate() {
+        for &won_idx in won {
+            // with wrapping a card can win the same card more than once
+            match won_from[won_idx].iter_mut().find(|(from, _)| *from == idx) {
+                Some((_, count)) => {
+                    *count = count
+                        .checked_add(copies[idx])
+                        .ok_or_else(|| format!("too many copies of card {}", cards[won_idx].id))?
+                }
+                None => won_from[won_idx].push((idx, copies[idx])),
+            }
         }
     }
 
-    num_scratch_cards_of_id.iter().sum()
+    // count_copies has already checked there are no cycles, so the rounds run out after at most
+    // one per card
+    let too_many = || "too many scratch cards in total".to_string();
+    let mut rounds = vec![];
+    let mut in_play = vec![];
+    let mut current = vec![1_u64; cards.len()];
+
+    while current.iter().any(|&count| count > 0) {
+        let new_cards = current
+            .iter()
+            .try_fold(0_u64, |total, &count| total.checked_add(count))
+            .ok_or_else(too_many)?;
+        let total = in_play
+            .last()
+            .map_or(Some(new_cards), |&total: &u64| total.checked_add(new_cards))
+            .ok_or_else(too_many)?;
+        rounds.push(new_cards);
+        in_play.push(total);
+
+        let mut next = vec![0_u64; cards.len()];
+        for (idx, won) in wins.iter().enumerate() {
+            for &won_idx in won {
+                next[won_idx] = next[won_idx]
+                    .checked_add(current[idx])
+                    .ok_or_else(too_many)?;
+            }
+        }
+        current = next;
+    }
+
+    Ok(Simulation {
+        copies,
+        won_from,
+        rounds,
+        in_play,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SimulationFormat {
+    Table,
+    Dot,
+}
+
+fn simulation_table(cards: &[Card], simulation: &Simulation) -> String {
+    let mut lines = vec![format!(
+        "{:>6}  {:>7}  {:>8}  won from",
+        "card", "matches", "copies"
+    )];
+
+    for (idx, card) in cards.iter().enumerate() {
+        let won_from = simulation.won_from[idx]
+            .iter()
+            .map(|&(from, count)| format!("card {} x{}", cards[from].id, count))
+            .collect::<Vec<String>>();
+
+        lines.push(format!(
+            "{:>6}  {:>7}  {:>8}  {}",
+            card.id,
+            card.matches(),
+            simulation.copies[idx],
+            if won_from.is_empty() {
+                "-".to_string()
+            } else {
+                won_from.join(", ")
+            }
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push(format!(
+        "{:>6}  {:>12}  {:>12}",
+        "round", "new cards", "in play"
+    ));
+
+    for (round, (&new_cards, &in_play)) in simulation
+        .rounds
+        .iter()
+        .zip(&simulation.in_play)
+        .enumerate()
+    {
+        lines.push(format!("{:>6}  {:>12}  {:>12}", round, new_cards, in_play));
+    }
+
+    lines.join("\n")
+}
+
+/// the provenance DAG in graphviz format: an edge from card a to card b is labelled with how many
+/// copies of b came from a
+fn simulation_dot(cards: &[Card], simulation: &Simulation) -> String {
+    let mut lines = vec!["digraph scratchcards {".to_string()];
+
+    for (idx, card) in cards.iter().enumerate() {
+        lines.push(format!(
+            "    card{} [label=\"Card {}\\n{} copies\"];",
+            card.id, card.id, simulation.copies[idx]
+        ));
+    }
+
+    for (idx, card) in cards.iter().enumerate() {
+        for &(from, count) in &simulation.won_from[idx] {
+            lines.push(format!(
+                "    card{} -> card{} [label=\"{}\"];",
+                cards[from].id, card.id, count
+            ));
+        }
+    }
+
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+#[derive(Debug)]
+struct Options {
+    input: Option<String>,
+    overflow: Overflow,
+    simulate: Option<SimulationFormat>,
+}
+
+/// reads `--input <path>`, `--overflow <clamp | wrap | error>` and `--simulate <table | dot>` from
+/// the command line
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        input: None,
+        overflow: Overflow::Error,
+        simulate: None,
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                options.input = Some(args.next().ok_or("--input requires a path")?.clone());
+            }
+            "--overflow" => {
+                options.overflow = match args.next().map(|s| s.as_str()) {
+                    Some("clamp") => Overflow::Clamp,
+                    Some("wrap") => Overflow::Wrap,
+                    Some("error") => Overflow::Error,
+                    Some(other) => return Err(format!("unknown overflow mode `{}`", other)),
+                    None => return Err("--overflow requires a mode".to_string()),
+                };
+            }
+            "--simulate" => {
+                options.simulate = Some(match args.next().map(|s| s.as_str()) {
+                    Some("table") => SimulationFormat::Table,
+                    Some("dot") => SimulationFormat::Dot,
+                    Some(other) => return Err(format!("unknown simulation format `{}`", other)),
+                    None => return Err("--simulate requires a format".to_string()),
+                });
+            }
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        }
+    }
+
+    Ok(options)
 }
 
 pub fn main() {
-    let card_lines = String::from_utf8(include_bytes!("sample_input_data/day4.txt").to_vec())
-        .unwrap()
-        .split("\n")
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    });
+
+    let input = match &options.input {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("error: could not read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => String::from_utf8(include_bytes!("sample_input_data/day4.txt").to_vec()).unwrap(),
+    };
+
+    let card_lines = input
+        .lines()
         .map(|s| s.to_string())
         .collect::<Vec<String>>();
 
-    println!("Part 1: {}", part1(&card_lines));
-    println!("Part 2: {}", part2(&card_lines));
+    let cards = parse_cards(&card_lines).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+
+    if let Some(format) = options.simulate {
+        let simulation = simulate(&cards, options.overflow).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        });
+        match format {
+            SimulationFormat::Table => println!("{}", simulation_table(&cards, &simulation)),
+            SimulationFormat::Dot => println!("{}", simulation_dot(&cards, &simulation)),
+        }
+        return;
+    }
+
+    let parts = [
+        ("Part 1", part1(&cards)),
+        ("Part 2", part2(&cards, options.overflow)),
+    ];
+    for (name, result) in parts {
+        match result {
+            Ok(total) => println!("{}: {}", name, total),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the last card wins more cards than there are left in the table
+    const DECK: &str = "Card 1: 1 2 | 1 5
+Card 2: 1 2 | 3 4
+Card 3: 1 2 3 | 1 2 3";
+
+    fn deck() -> Vec<Card> {
+        let lines = DECK.lines().map(|s| s.to_string()).collect::<Vec<String>>();
+        parse_cards(&lines).unwrap()
+    }
+
+    #[test]
+    fn sample() {
+        let lines = include_str!("sample_input_data/day4.txt")
+            .lines()
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>();
+        let cards = parse_cards(&lines).unwrap();
+        assert_eq!(part1(&cards), Ok(13));
+        assert_eq!(part2(&cards, Overflow::Clamp), Ok(30));
+    }
+
+    #[test]
+    fn clamp_drops_cards_past_the_end() {
+        let cards = deck();
+        assert_eq!(part1(&cards), Ok(5));
+        assert_eq!(count_copies(&cards, Overflow::Clamp), Ok(vec![1, 2, 1]));
+        assert_eq!(part2(&cards, Overflow::Clamp), Ok(4));
+    }
+
+    #[test]
+    fn error_names_the_card_winning_past_the_end() {
+        assert_eq!(
+            part2(&deck(), Overflow::Error),
+            Err("card 3 wins 3 cards, but only 0 come after it".to_string())
+        );
+    }
+
+    #[test]
+    fn wrap_rejects_a_card_winning_copies_of_itself() {
+        assert_eq!(
+            part2(&deck(), Overflow::Wrap),
+            Err("card 3 keeps winning copies of itself, the cascade never ends".to_string())
+        );
+    }
 }
@@ -1,179 +1,339 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet},
+    env, fs,
     ops::Range,
+    process,
 };
 
-type RangeMap = (Range<u64>, Range<u64>);
-type AlmanacData = HashMap<String, Vec<RangeMap>>;
+use aoc_2023::{interval_map::IntervalMap, range_set::RangeSet};
+
+/// one `<from>-to-<to> map:` section of the almanac
+#[derive(Debug)]
+struct AlmanacMap {
+    from: String,
+    to: String,
+    map: IntervalMap,
+}
+
+type AlmanacData = Vec<AlmanacMap>;
 
 fn parse_sections_of_almanac(string: String) -> (Vec<u64>, AlmanacData) {
     let sections = string.split("\n\n").collect::<Vec<&str>>();
 
-    let mut parsed_data: AlmanacData = HashMap::new();
+    let mut parsed_data: AlmanacData = vec![];
     let mut seeds = vec![];
 
     for section in sections {
         if section.starts_with("seeds") {
             seeds = section
-                .split(":")
+                .split(':')
                 .nth(1)
                 .unwrap()
-                .trim()
-                .split(" ")
+                .split_whitespace()
                 .map(|num_str| num_str.parse::<u64>().unwrap())
                 .collect::<Vec<u64>>();
         } else {
-            let mut lines = section.split("\n");
-            let map_name = lines.nth(0).unwrap().replace("map:", "").trim().to_string(); // removes first line
+            let mut lines = section.lines();
+            let map_name = lines.next().unwrap().replace("map:", "").trim().to_string(); // removes first line
+            let (from, to) = map_name
+                .split_once("-to-")
+                .unwrap_or_else(|| panic!("Error parsing map header: {}", map_name));
 
-            let mut range_maps: Vec<RangeMap> = vec![];
+            let segments = lines
+                .filter(|line| !line.trim().is_empty())
+                .map(|range_line| {
+                    let nums = range_line
+                        .split_whitespace()
+                        .map(|num_str| num_str.parse::<u64>().unwrap())
+                        .collect::<Vec<u64>>();
 
-            for range_line in lines {
-                let nums = range_line
-                    .split(" ")
-                    .map(|num_str| num_str.parse::<u64>().unwrap())
-                    .collect::<Vec<u64>>();
+                    let destination_range_start = nums[0];
+                    let source_range_start = nums[1];
+                    let range_length = nums[2];
 
-                let destination_range_start = nums[0];
-                let source_range_start = nums[1];
-                let range_length = nums[2];
+                    (
+                        source_range_start..source_range_start + range_length,
+                        destination_range_start,
+                    )
+                });
 
-                range_maps.push((
-                    source_range_start..source_range_start + range_length,
-                    destination_range_start..destination_range_start + range_length,
-                ))
-            }
+            let map = IntervalMap::from_segments(segments)
+                .unwrap_or_else(|e| panic!("Error parsing {} map: {}", map_name, e));
 
-            parsed_data.insert(map_name.to_string(), range_maps);
+            parsed_data.push(AlmanacMap {
+                from: from.to_string(),
+                to: to.to_string(),
+                map,
+            });
         }
     }
 
     (seeds, parsed_data)
 }
 
-fn get_overlapping(
-    test_range: &Range<u64>,
-    source_range: &Range<u64>,
-) -> (Option<Range<u64>>, Vec<Range<u64>>) {
-    let mut overlapping_range = None;
-    let mut remaining_ranges = vec![];
-
-    if test_range.start >= source_range.start && test_range.start < source_range.end {
-        // SSSSSSS
-        //  TTTT
-        if test_range.end > source_range.end {
-            // SSSSSSS
-            //    TTTTT
-            overlapping_range = Some(test_range.start..source_range.end);
-            remaining_ranges.push(source_range.end..test_range.end);
-        } else {
-            overlapping_range = Some(test_range.clone());
-        }
-    } else if test_range.start < source_range.start && test_range.end > source_range.start {
-        remaining_ranges.push(test_range.start..source_range.start);
-        if test_range.end <= source_range.end {
-            //   SSSSSSS
-            // TTTTT
-            overlapping_range = Some(source_range.start..test_range.end);
-        } else if test_range.end > source_range.end {
-            //   SSSSSSS
-            // TTTTTTTTTTT
-            overlapping_range = Some(source_range.clone());
-            remaining_ranges.push(source_range.end..test_range.end);
+/// follows the maps from category `from` to category `to`. each category can only lead to one
+/// other, so there's at most one way through
+fn find_chain<'a>(
+    almanac_data: &'a AlmanacData,
+    from: &str,
+    to: &str,
+) -> Result<Vec<&'a AlmanacMap>, String> {
+    let mut outgoing: HashMap<&str, Vec<&AlmanacMap>> = HashMap::new();
+    for map in almanac_data {
+        outgoing.entry(&map.from).or_default().push(map);
+    }
+
+    let mut chain = vec![];
+    let mut category = from;
+    let mut visited = HashSet::from([from]);
+
+    while category != to {
+        let map = match outgoing.get(category).map(|maps| maps.as_slice()) {
+            Some([map]) => *map,
+            Some(maps) => {
+                return Err(format!(
+                    "category `{}` branches into {}, can't pick a path to `{}`",
+                    category,
+                    maps.iter()
+                        .map(|map| format!("`{}`", map.to))
+                        .collect::<Vec<String>>()
+                        .join(" and "),
+                    to
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "no map from `{}` on the way from `{}` to `{}`",
+                    category, from, to
+                ))
+            }
+        };
+
+        if !visited.insert(&map.to) {
+            return Err(format!(
+                "maps from `{}` loop back to `{}` without reaching `{}`",
+                from, map.to, to
+            ));
         }
+
+        chain.push(map);
+        category = &map.to;
     }
 
-    (overlapping_range, remaining_ranges)
+    Ok(chain)
 }
 
-fn get_dest_ranges_from_seed_range(seed_range: Range<u64>, almanac_data: &AlmanacData) -> Vec<Range<u64>> {
-    let maps = vec![
-        "seed-to-soil",
-        "soil-to-fertilizer",
-        "fertilizer-to-water",
-        "water-to-light",
-        "light-to-temperature",
-        "temperature-to-humidity",
-        "humidity-to-location",
-    ]
-    .iter()
-    .map(|&map_name| &almanac_data[map_name])
-    .collect::<Vec<&Vec<RangeMap>>>();
+/// the whole chain as a single map
+fn compose_chain(chain: &[&AlmanacMap]) -> IntervalMap {
+    chain
+        .iter()
+        .fold(IntervalMap::new(), |composed, almanac_map| {
+            composed.then(&almanac_map.map)
+        })
+}
 
-    let mut curr_ranges: Vec<Range<u64>> = [seed_range].to_vec();
-    let mut dest_ranges: Vec<Range<u64>> = vec![];
+/// the seed list read as `start length` pairs, as in part 2
+fn seed_ranges(seeds: &[u64]) -> RangeSet {
+    seeds
+        .chunks_exact(2)
+        .map(|seed_range| seed_range[0]..seed_range[0] + seed_range[1])
+        .collect()
+}
 
-    for map in maps {
-        dest_ranges = vec![];
+fn part1(seeds: &[u64], seed_to_location: &IntervalMap) -> u64 {
+    let seeds = seeds.iter().copied().collect::<RangeSet>();
+    seed_to_location.map_set(&seeds).min().unwrap()
+}
 
-        // create queue of current ranges that need to be mapped to dest_ranges
-        let mut curr_range_queue = VecDeque::from(curr_ranges);
+fn part2(seeds: &[u64], seed_to_location: &IntervalMap) -> u64 {
+    seed_to_location.map_set(&seed_ranges(seeds)).min().unwrap()
+}
 
-        while !curr_range_queue.is_empty() {
-            let curr_range = &mut curr_range_queue.pop_front().unwrap();
+/// the seed in `seed_ranges` that ends up at the lowest location. within each piece of a range
+/// the mapping is a plain shift, so the lowest location of a piece comes from its first seed
+fn lowest_seed_in_ranges(seed_ranges: &RangeSet, seed_to_location: &IntervalMap) -> Option<u64> {
+    seed_ranges
+        .ranges()
+        .iter()
+        .flat_map(|seed_range| seed_to_location.map_pieces(seed_range.clone()))
+        .min_by_key(|(_, dest)| dest.start)
+        .map(|(source, _)| source.start)
+}
 
-            let mut found_overlap = false;
+/// the value of `seed` in every category along the chain, starting with the seed itself
+fn provenance(seed: u64, from: &str, chain: &[&AlmanacMap]) -> Vec<(String, u64)> {
+    let mut values = vec![(from.to_string(), seed)];
+    let mut value = seed;
 
-            for ranges in map {
-                let source_range = &ranges.0;
-                let destination_range = &ranges.1;
+    for almanac_map in chain {
+        value = almanac_map.map.map(value);
+        values.push((almanac_map.to.clone(), value));
+    }
 
-                // check overlap
-                let (overlap, remaining) = get_overlapping(curr_range, source_range);
+    values
+}
 
-                if let Some(overlapping_range) = overlap {
-                    dest_ranges.push(
-                        destination_range.start + overlapping_range.start - source_range.start
-                            ..destination_range.start + overlapping_range.end - source_range.start,
-                    );
+fn format_provenance(values: &[(String, u64)]) -> String {
+    values
+        .iter()
+        .map(|(category, value)| format!("{} {}", category, value))
+        .collect::<Vec<String>>()
+        .join(" -> ")
+}
 
-                    // add the remaining ranges to the queue, to map them separately
-                    remaining
-                        .iter()
-                        .for_each(|r| curr_range_queue.push_back(r.clone()));
-                    found_overlap = true;
-                    break;
-                }
-            }
+fn format_ranges(ranges: &[Range<u64>]) -> String {
+    if ranges.is_empty() {
+        return "none".to_string();
+    }
+    ranges
+        .iter()
+        .map(|range| format!("{}..{}", range.start, range.end))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
 
-            if !found_overlap {
-                dest_ranges.push(curr_range.clone());
-            }
-        }
-        curr_ranges = dest_ranges.clone();
+/// how the lowest location is reached in both parts, category by category
+fn explain(
+    seeds: &[u64],
+    from: &str,
+    chain: &[&AlmanacMap],
+    seed_to_location: &IntervalMap,
+) -> String {
+    let mut lines = vec![];
+
+    if let Some(&seed) = seeds
+        .iter()
+        .min_by_key(|&&seed_no| seed_to_location.map(seed_no))
+    {
+        lines.push(format!(
+            "part 1: {}",
+            format_provenance(&provenance(seed, from, chain))
+        ));
     }
 
-    dest_ranges
+    if let Some(seed) = lowest_seed_in_ranges(&seed_ranges(seeds), seed_to_location) {
+        lines.push(format!(
+            "part 2: {}",
+            format_provenance(&provenance(seed, from, chain))
+        ));
+    }
+
+    lines.join("\n")
 }
 
-fn part1(seeds: &Vec<u64>, parsed_data: &AlmanacData) -> u64 {
-    seeds
-        .iter()
-        .map(|seed_no| get_dest_ranges_from_seed_range(*seed_no..*seed_no, parsed_data)[0].start)
-        .min()
-        .unwrap()
+/// the source ranges that map into `target`, and which of those the almanac's seed ranges cover
+fn reverse(seeds: &[u64], seed_to_location: &IntervalMap, target: Range<u64>) -> String {
+    let sources = seed_to_location.inverse_set(&RangeSet::from_iter([target.clone()]));
+    let covered = sources.intersection(&seed_ranges(seeds));
+
+    [
+        format!(
+            "maps into {}..{}: {}",
+            target.start,
+            target.end,
+            format_ranges(sources.ranges())
+        ),
+        format!(
+            "within the seed ranges: {}",
+            format_ranges(covered.ranges())
+        ),
+    ]
+    .join("\n")
 }
 
-fn part2(seeds: &Vec<u64>, parsed_data: &AlmanacData) -> u64 {
-    seeds
-        .windows(2)
-        .step_by(2)
-        .map(|seed_range| {
-            get_dest_ranges_from_seed_range(seed_range[0]..seed_range[0] + seed_range[1], parsed_data)
-                .iter()
-                .map(|dest_range| dest_range.start)
-                .min()
-                .unwrap()
-        })
-        .min()
-        .unwrap()
+#[derive(Debug)]
+struct Options {
+    input: Option<String>,
+    print_map: bool,
+    from: String,
+    to: String,
+    explain: bool,
+    reverse: Option<Range<u64>>,
+}
+
+/// reads `--input <path>`, `--print-map`, `--from <category>`, `--to <category>`, `--explain` and
+/// `--reverse <start>..<end>` from the command line
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        input: None,
+        print_map: false,
+        from: "seed".to_string(),
+        to: "location".to_string(),
+        explain: false,
+        reverse: None,
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                options.input = Some(args.next().ok_or("--input requires a path")?.clone());
+            }
+            "--print-map" => options.print_map = true,
+            "--from" => {
+                options.from = args.next().ok_or("--from requires a category")?.clone();
+            }
+            "--to" => {
+                options.to = args.next().ok_or("--to requires a category")?.clone();
+            }
+            "--explain" => options.explain = true,
+            "--reverse" => {
+                let range = args.next().ok_or("--reverse requires a range")?;
+                let invalid = || format!("invalid range `{}`, expected <start>..<end>", range);
+                let (start, end) = range.split_once("..").ok_or_else(invalid)?;
+                options.reverse = Some(
+                    start.parse::<u64>().map_err(|_| invalid())?
+                        ..end.parse::<u64>().map_err(|_| invalid())?,
+                );
+            }
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        }
+    }
+
+    Ok(options)
 }
 
 pub fn main() {
-    let (seeds, parsed_data) = parse_sections_of_almanac(
-        String::from_utf8(include_bytes!("sample_input_data/day5.txt").to_vec()).unwrap(),
-    );
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    });
+
+    let input = match &options.input {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("error: could not read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => String::from_utf8(include_bytes!("sample_input_data/day5.txt").to_vec()).unwrap(),
+    };
+
+    let (seeds, parsed_data) = parse_sections_of_almanac(input);
+
+    let chain = find_chain(&parsed_data, &options.from, &options.to).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+    let seed_to_location = compose_chain(&chain);
+
+    if options.print_map {
+        print!("{}", seed_to_location);
+        return;
+    }
+
+    if let Some(target) = options.reverse {
+        println!("{}", reverse(&seeds, &seed_to_location, target));
+        return;
+    }
+
+    if options.explain {
+        println!(
+            "{}",
+            explain(&seeds, &options.from, &chain, &seed_to_location)
+        );
+        return;
+    }
 
-    println!("{:?}", part1(&seeds, &parsed_data));
-    println!("{:?}", part2(&seeds, &parsed_data));
+    println!("{:?}", part1(&seeds, &seed_to_location));
+    println!("{:?}", part2(&seeds, &seed_to_location));
 }
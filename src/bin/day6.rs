@@ -1,103 +1,525 @@
+use std::{env, fs, ops::RangeInclusive, process};
+
+#[derive(Debug)]
 struct Race {
-    time: u64,
-    distance: u64,
+    time: u128,
+    distance: u128,
+}
+
+/// floor of the square root of `n`, exactly
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+
+    // newton's method from an estimate that is at least the root, so it decreases monotonically
+    let mut x = 1_u128 << (128 - n.leading_zeros()).div_ceil(2);
+    loop {
+        let next = (x + n / x) / 2;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
 }
 
-fn find_no_of_ways_to_beat_race(race: &Race) -> u64 {
-    /* 
-        solving inequality n(t - n) > d for n, we get the upper and lower limits
-        of how long we can hold down button
+/// the hold times that win a plain race, where holding for n ms out of t goes n(t - n) mm.
+/// returns `None` if the product gets too big to work with, or `Some(None)` if nothing wins
+fn classic_winning_holds(time: u128, distance: u128) -> Option<Option<RangeInclusive<u128>>> {
+    /*
+        we need n(t - n) > d. writing t = 2h + p (p being 0 or 1) and n = h - k, that is
+        (h - k)(h + p + k) = h(h + p) - k(k + p) > d, so the winning holds are the ones within k
+        of the middle, for every k with k(k + p) < h(h + p) - d.
+
+        everything is done in integers, so exact roots don't sneak in as a win: the bound is
+        strict and checked as such
     */
-    let t = race.time as f64;
-    let d = race.distance as f64;
-    let discriminant = (t * t - 4.0 * d).sqrt();
+    let (h, p) = (time / 2, time % 2);
+    let max_distance = h.checked_mul(h + p)?;
+
+    if max_distance <= distance {
+        return Some(None);
+    }
 
-    let lower_lim = ((t - discriminant) / 2.0).ceil() as u64;
-    let upper_lim = ((t + discriminant) / 2.0).floor() as u64;
+    // largest k with k(k + p) <= m
+    let m = max_distance - distance - 1;
+    let mut k = isqrt(m);
+    if p == 1 && k.checked_mul(k + 1).is_none_or(|v| v > m) {
+        k -= 1;
+    }
 
-    return upper_lim - lower_lim + 1;
+    Some(Some(h - k..=h + p + k))
 }
 
-fn parse_kerned_input(lines: &Vec<String>) -> Race {
-    let mut race = Race {
-        time: 0,
-        distance: 0,
-    };
+/// how the boat moves. the puzzle's boat is the default: 1 mm/ms of speed per ms held, moving as
+/// soon as the button is released, with no top speed and no drag
+#[derive(Debug, Clone, PartialEq)]
+struct RaceModel {
+    /// speed gained per ms the button is held
+    acceleration: u128,
+    /// ms after releasing the button before the boat starts moving
+    startup_delay: u128,
+    top_speed: Option<u128>,
+    /// speed lost per ms of moving. the boat stops once its speed reaches zero
+    drag: u128,
+}
 
-    for line in lines {
-        let mut line_split = line.split(":");
-        let label = line_split.nth(0).unwrap();
-        let value = line_split
-            .nth(0)
-            .unwrap()
-            .split_whitespace()
-            .map(|n| n.trim())
-            .fold(String::new(), |a, b| a + b)
-            .parse::<u64>()
-            .unwrap();
-
-        if label == "Time" {
-            race.time = value
-        } else {
-            race.distance = value;
+impl Default for RaceModel {
+    fn default() -> Self {
+        RaceModel {
+            acceleration: 1,
+            startup_delay: 0,
+            top_speed: None,
+            drag: 0,
         }
     }
-
-    race
 }
 
-fn parse_races(lines: &Vec<String>) -> Vec<Race> {
-    let mut times = vec![];
-    let mut distances = vec![];
+impl RaceModel {
+    fn speed(&self, hold: u128) -> u128 {
+        let speed = self.acceleration.saturating_mul(hold);
+        self.top_speed
+            .map_or(speed, |top_speed| speed.min(top_speed))
+    }
 
-    for line in lines {
-        let mut line_split = line.split(":");
-        let label = line_split.nth(0).unwrap();
-        let values = line_split
-            .nth(0)
-            .unwrap()
-            .split_whitespace()
-            .map(|n| n.parse::<u64>().unwrap())
-            .collect::<Vec<u64>>();
+    /// how far the boat goes, or `None` if it's further than a u128 can hold
+    fn distance(&self, race: &Race, hold: u128) -> Option<u128> {
+        let speed = self.speed(hold);
+        let moving = race
+            .time
+            .saturating_sub(hold)
+            .saturating_sub(self.startup_delay);
 
-        if label == "Time" {
-            times = values;
-        } else {
-            distances = values;
+        if self.drag == 0 {
+            return speed.checked_mul(moving);
         }
+
+        // the boat moves at speed, speed - drag, speed - 2 * drag, ... for as long as that's
+        // positive and the race lasts
+        let steps = moving.min(speed.div_ceil(self.drag));
+        steps
+            .checked_mul(speed)?
+            .checked_sub(self.drag.checked_mul(steps * steps.saturating_sub(1) / 2)?)
+    }
+
+    fn beats(&self, race: &Race, hold: u128) -> bool {
+        self.distance(race, hold)
+            .is_none_or(|distance| distance > race.distance)
     }
 
-    let mut races = Vec::new();
+    /// the winning hold times, worked out from the formula where there is one
+    fn winning_holds(&self, race: &Race) -> Option<RangeInclusive<u128>> {
+        self.analytic_winning_holds(race)
+            .unwrap_or_else(|| self.search_winning_holds(race))
+    }
 
-    for (idx, &time) in times.iter().enumerate() {
-        let d = distances[idx];
-        races.push(Race { time, distance: d })
+    fn ways_to_win(&self, race: &Race) -> u128 {
+        self.winning_holds(race)
+            .map_or(0, |holds| holds.end() - holds.start() + 1)
     }
 
-    races
+    /// without drag, the distance is a(t' - n)n until the top speed kicks in (t' being the race
+    /// time less the startup delay), which is the plain race scaled by a, and then c(t' - n) once
+    /// the speed is capped at c. returns `None` if that can't be worked out without overflowing
+    fn analytic_winning_holds(&self, race: &Race) -> Option<Option<RangeInclusive<u128>>> {
+        if self.drag != 0 {
+            return None;
+        }
+        if self.acceleration == 0 || self.top_speed == Some(0) {
+            return Some(None);
+        }
+
+        let time = race.time.saturating_sub(self.startup_delay);
+        // first hold time at which the speed is capped
+        let capped_from = self
+            .top_speed
+            .map_or(u128::MAX, |top_speed| top_speed.div_ceil(self.acceleration));
+
+        // a * x > d is the same as x > d / a, rounding down
+        let uncapped = classic_winning_holds(time, race.distance / self.acceleration)?
+            .filter(|holds| *holds.start() < capped_from)
+            .map(|holds| *holds.start()..=(*holds.end()).min(capped_from - 1));
+
+        // c(t' - n) > d is the same as t' - n > d / c, rounding down
+        let capped = self.top_speed.and_then(|top_speed| {
+            let last = time.checked_sub(race.distance / top_speed + 1)?;
+            (capped_from <= last).then_some(capped_from..=last)
+        });
+
+        Some(match (uncapped, capped) {
+            (Some(a), Some(b)) => Some(*a.start()..=*b.end()),
+            (a, b) => a.or(b),
+        })
+    }
+
+    /// finds the winning hold times by searching, relying on the distance rising to a peak and
+    /// then falling: the speed only grows with the hold time while the time left to move only
+    /// shrinks
+    fn search_winning_holds(&self, race: &Race) -> Option<RangeInclusive<u128>> {
+        // order distances with "too far to count" above everything else
+        let key = |hold| match self.distance(race, hold) {
+            Some(distance) => (false, distance),
+            None => (true, 0),
+        };
+
+        // smallest hold time that goes at least as far as every longer one
+        let peak = partition_point(0, race.time, |hold| key(hold + 1) > key(hold));
+        if !self.beats(race, peak) {
+            return None;
+        }
+
+        let first = partition_point(0, peak, |hold| !self.beats(race, hold));
+        let last = partition_point(peak, race.time, |hold| self.beats(race, hold + 1));
+
+        Some(first..=last)
+    }
+}
+
+/// the first value in `lo..=hi` for which `pred` is false, given `pred` is true up to some point
+/// and false after it. returns `hi` if `pred` holds for everything before it
+fn partition_point(mut lo: u128, mut hi: u128, pred: impl Fn(u128) -> bool) -> u128 {
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+/// how the numbers on each row are read
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kerning {
+    /// every column is its own race
+    PerColumn,
+    /// the spaces are bad kerning, and each row is one big number
+    Kerned,
+}
+
+/// the races run against one row of record distances
+#[derive(Debug)]
+struct Heat {
+    /// the label of the distance row, e.g. `Distance` or `Distance (heat 2)`
+    name: String,
+    races: Vec<Race>,
 }
 
-fn part1(races: &Vec<Race>) -> u64 {
-    races
+fn parse_row(values: &str, kerning: Kerning, line_no: usize) -> Result<Vec<u128>, String> {
+    let numbers = match kerning {
+        Kerning::PerColumn => values.split_whitespace().map(|n| n.to_string()).collect(),
+        Kerning::Kerned => vec![values.split_whitespace().collect::<String>()],
+    };
+
+    numbers
         .iter()
-        .map(|race| find_no_of_ways_to_beat_race(race))
-        .product()
+        .filter(|n| !n.is_empty())
+        .map(|n| {
+            n.parse::<u128>()
+                .map_err(|_| format!("line {}: `{}` is not a valid number", line_no, n))
+        })
+        .collect()
+}
+
+/// parses `Time:` and `Distance:` rows into heats. each `Time` row is paired with every
+/// `Distance` row up to the next `Time` row, so the records of several heats over the same times
+/// can be listed one after another. anything after the first word of a label (like `(heat 2)`)
+/// just names the heat
+fn parse_heats(lines: &[String], kerning: Kerning) -> Result<Vec<Heat>, String> {
+    let mut heats = vec![];
+    // the current times, the line they are on, and whether any distances have used them yet
+    let mut times: Option<(usize, Vec<u128>, bool)> = None;
+    let unused =
+        |time_line_no: usize| format!("line {}: times without any distances", time_line_no);
+
+    for (idx, line) in lines.iter().enumerate() {
+        let line_no = idx + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (label, values) = line
+            .split_once(':')
+            .ok_or_else(|| format!("line {}: expected `<label>: <numbers>`", line_no))?;
+        let values = parse_row(values, kerning, line_no)?;
+
+        match label.split_whitespace().next() {
+            Some("Time") => {
+                if values.is_empty() {
+                    return Err(format!("line {}: no times", line_no));
+                }
+                if let Some((time_line_no, _, false)) = times {
+                    return Err(unused(time_line_no));
+                }
+                times = Some((line_no, values, false));
+            }
+            Some("Distance") => {
+                let (time_line_no, times, used) = times
+                    .as_mut()
+                    .ok_or_else(|| format!("line {}: distances come before any times", line_no))?;
+
+                if values.is_empty() {
+                    return Err(format!("line {}: no distances", line_no));
+                }
+                if times.len() != values.len() {
+                    return Err(format!(
+                        "line {} has {} distances, but line {} has {} times",
+                        line_no,
+                        values.len(),
+                        time_line_no,
+                        times.len()
+                    ));
+                }
+
+                *used = true;
+                heats.push(Heat {
+                    name: label.trim().to_string(),
+                    races: times
+                        .iter()
+                        .zip(values)
+                        .map(|(&time, distance)| Race { time, distance })
+                        .collect(),
+                });
+            }
+            _ => {
+                return Err(format!(
+                    "line {}: unknown label `{}`",
+                    line_no,
+                    label.trim()
+                ))
+            }
+        }
+    }
+
+    if let Some((time_line_no, _, false)) = times {
+        return Err(unused(time_line_no));
+    }
+    if heats.is_empty() {
+        return Err("no races found".to_string());
+    }
+
+    Ok(heats)
 }
 
-fn part2(race: &Race) -> u64 {
-    find_no_of_ways_to_beat_race(race)
+/// the answer to both parts: with kerning fixed there's just one race per heat to multiply
+fn product_of_ways(races: &[Race], model: &RaceModel) -> u128 {
+    races.iter().map(|race| model.ways_to_win(race)).product()
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    input: Option<String>,
+    model: RaceModel,
+}
+
+/// reads `--input <path>` and the race model (`--acceleration <speed per ms>`,
+/// `--startup-delay <ms>`, `--top-speed <speed>`, `--drag <speed per ms>`) from the command line
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{} requires a value", arg))?;
+            value
+                .parse::<u128>()
+                .map_err(|_| format!("invalid value `{}` for {}", value, arg))
+        };
+
+        match arg.as_str() {
+            "--input" => {
+                options.input = Some(args.next().ok_or("--input requires a path")?.clone());
+            }
+            "--acceleration" => options.model.acceleration = value()?,
+            "--startup-delay" => options.model.startup_delay = value()?,
+            "--top-speed" => options.model.top_speed = Some(value()?),
+            "--drag" => options.model.drag = value()?,
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        }
+    }
+
+    Ok(options)
 }
 
 pub fn main() {
-    let lines = String::from_utf8(include_bytes!("sample_input_data/day6.txt").to_vec())
-        .unwrap()
-        .split("\n")
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    });
+
+    let input = match &options.input {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("error: could not read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => String::from_utf8(include_bytes!("sample_input_data/day6.txt").to_vec()).unwrap(),
+    };
+
+    let lines = input
+        .lines()
         .map(|line| line.to_string())
         .collect::<Vec<String>>();
 
-    let races = parse_races(&lines);
-    println!("part 1: {}", part1(&races));
+    let parse = |kerning| {
+        parse_heats(&lines, kerning).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        })
+    };
 
-    let race = parse_kerned_input(&lines);
-    println!("part 2: {}", part2(&race));
+    for (part, kerning) in [("part 1", Kerning::PerColumn), ("part 2", Kerning::Kerned)] {
+        let heats = parse(kerning);
+        for heat in &heats {
+            if heats.len() == 1 {
+                println!("{}: {}", part, product_of_ways(&heat.races, &options.model));
+            } else {
+                println!(
+                    "{} ({}): {}",
+                    part,
+                    heat.name,
+                    product_of_ways(&heat.races, &options.model)
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// counts winning hold times one at a time
+    fn count_by_brute_force(model: &RaceModel, race: &Race) -> u128 {
+        (0..=race.time)
+            .filter(|&hold| model.beats(race, hold))
+            .count() as u128
+    }
+
+    /// a fixed, well spread out sequence of values to pick test races with
+    fn spread(seed: u64) -> impl FnMut() -> u64 {
+        let mut state = seed;
+        move || {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mixed = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            let mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            mixed ^ (mixed >> 31)
+        }
+    }
+
+    #[test]
+    fn sample() {
+        let lines = include_str!("sample_input_data/day6.txt")
+            .lines()
+            .map(|line| line.to_string())
+            .collect::<Vec<String>>();
+        let model = RaceModel::default();
+
+        let heats = parse_heats(&lines, Kerning::PerColumn).unwrap();
+        assert_eq!(product_of_ways(&heats[0].races, &model), 288);
+        let heats = parse_heats(&lines, Kerning::Kerned).unwrap();
+        assert_eq!(product_of_ways(&heats[0].races, &model), 71503);
+    }
+
+    #[test]
+    fn rejects_empty_and_unused_rows() {
+        let parse = |input: &str| {
+            let lines = input
+                .lines()
+                .map(|line| line.to_string())
+                .collect::<Vec<String>>();
+            parse_heats(&lines, Kerning::PerColumn).map(|heats| heats.len())
+        };
+
+        assert_eq!(
+            parse("Time:\nDistance:"),
+            Err("line 1: no times".to_string())
+        );
+        assert_eq!(
+            parse("Time: 7\nDistance:"),
+            Err("line 2: no distances".to_string())
+        );
+        assert_eq!(
+            parse("Time: 7\nDistance: 9\nTime: 8"),
+            Err("line 3: times without any distances".to_string())
+        );
+        assert_eq!(
+            parse("Time: 7\nTime: 8\nDistance: 9"),
+            Err("line 1: times without any distances".to_string())
+        );
+        assert_eq!(parse("Time: 7\nDistance: 9\nDistance (heat 2): 8"), Ok(2));
+    }
+
+    #[test]
+    fn classic_matches_brute_force_on_small_races() {
+        let classic = RaceModel::default();
+        for time in 0..=200 {
+            for distance in 0..=(time * time / 4 + 1) {
+                let race = Race { time, distance };
+                assert_eq!(
+                    classic.ways_to_win(&race),
+                    count_by_brute_force(&classic, &race),
+                    "{:?}",
+                    race
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn classic_matches_search_on_large_races() {
+        let classic = RaceModel::default();
+        let mut next = spread(1);
+        for _ in 0..20_000 {
+            let time = ((next() as u128) << 64 | next() as u128) >> (next() % 128);
+            let hold = next() as u128 % (time / 2 + 1);
+            // a distance that is exactly reached by `hold`, nudged by at most one either way
+            let distance = match hold.checked_mul(time - hold) {
+                Some(d) => (d + 1).saturating_sub(next() as u128 % 3),
+                None => next() as u128,
+            };
+
+            let race = Race { time, distance };
+            let searched = classic
+                .search_winning_holds(&race)
+                .map_or(0, |holds| holds.end() - holds.start() + 1);
+            assert_eq!(classic.ways_to_win(&race), searched, "{:?}", race);
+        }
+    }
+
+    #[test]
+    fn models_match_brute_force() {
+        let mut next = spread(2);
+        for _ in 0..20_000 {
+            let model = RaceModel {
+                acceleration: next() as u128 % 4,
+                startup_delay: next() as u128 % 5,
+                top_speed: next().is_multiple_of(2).then(|| next() as u128 % 30),
+                drag: if next().is_multiple_of(2) {
+                    0
+                } else {
+                    next() as u128 % 4
+                },
+            };
+            let time = next() as u128 % 60;
+            let distance = next() as u128 % (time * time + 1);
+            let race = Race { time, distance };
+
+            let expected = count_by_brute_force(&model, &race);
+            let searched = model
+                .search_winning_holds(&race)
+                .map_or(0, |holds| holds.end() - holds.start() + 1);
+            assert_eq!(
+                model.ways_to_win(&race),
+                expected,
+                "{:?}, {:?}",
+                model,
+                race
+            );
+            assert_eq!(searched, expected, "{:?}, {:?}", model, race);
+        }
+    }
 }
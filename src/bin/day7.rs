@@ -1,148 +1,861 @@
-use std::{cmp::Ordering, collections::HashMap};
+use std::{cmp::Reverse, collections::HashMap, env, fs, process};
 
-#[derive(Debug, PartialEq, PartialOrd)]
-enum HandType {
-    HighCard = 0,
-    OnePair = 1,
-    TwoPair = 2,
-    ThreeOfAKind = 3,
-    FullHouse = 4,
-    FourOfAKind = 5,
-    FiveOfAKind = 6,
+use aoc_2023::escape::{csv_field, json_string};
+
+/// a card: its rank by strength (0 is the weakest rank in the rule set it was parsed with) and,
+/// if the rule set has suits, its suit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Card {
+    rank: u8,
+    suit: Option<u8>,
+}
+
+/// what a hand needs to be of a given type
+#[derive(Debug, Clone, PartialEq)]
+struct HandTypeDef {
+    name: String,
+    /// how the cards have to group by rank, largest group first. `None` means any grouping
+    groups: Option<Vec<usize>>,
+    /// every card has a different rank and the ranks run consecutively
+    straight: bool,
+    /// every card has the same suit
+    flush: bool,
 }
 
-fn count_each_character(string: &String) -> HashMap<char, usize> {
-    let mut char_map = HashMap::new();
-    for c in string.chars() {
-        char_map.insert(
-            c,
-            if char_map.contains_key(&c) {
-                char_map[&c] + 1
-            } else {
-                1
-            },
-        );
+impl HandTypeDef {
+    fn groups(name: &str, groups: &[usize]) -> HandTypeDef {
+        HandTypeDef {
+            name: name.to_string(),
+            groups: Some(groups.to_vec()),
+            straight: false,
+            flush: false,
+        }
+    }
+
+    fn any(name: &str) -> HandTypeDef {
+        HandTypeDef {
+            name: name.to_string(),
+            groups: None,
+            straight: false,
+            flush: false,
+        }
     }
-    char_map
 }
 
-fn get_best_hand_with_joker(hand: &String) -> HandType {
-    // get best hand by trial and error: replacing J with each of the other cards
+/// how two hands of the same type are ordered
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TieBreak {
+    /// card by card, in the order they were dealt
+    InOrder,
+    /// strongest card first
+    Sorted,
+    /// the largest group first, then by rank, like poker
+    Grouped,
+    /// hands of the same type tie
+    None,
+}
+
+/// how hand types made of nothing but groups of equal cards are ordered
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TypeOrder {
+    /// the bigger the largest group the better, then the bigger the next one, and so on. with five
+    /// cards this is the puzzle's order
+    LargestGroup,
+    /// the fewer groups the better, then as `LargestGroup`
+    FewestGroups,
+}
+
+/// where a rule set's hand types come from
+#[derive(Debug, Clone, PartialEq)]
+enum HandTypes {
+    /// these, weakest first. a hand is of the strongest one it qualifies for
+    Listed(Vec<HandTypeDef>),
+    /// every way of grouping the cards by rank is a type of its own, for any hand size
+    ByGroups(TypeOrder),
+}
+
+/// everything that decides how hands are ranked
+#[derive(Debug, Clone, PartialEq)]
+struct RuleSet {
+    /// card ranks, weakest first
+    ranks: Vec<char>,
+    /// suit characters. if there are any, every card is written as its rank followed by its suit
+    suits: Vec<char>,
+    /// ranks that stand in for whatever card makes the best hand. they still rank as themselves
+    /// when breaking ties
+    wild: Vec<char>,
+    hand_size: usize,
+    hand_types: HandTypes,
+    tie_break: TieBreak,
+}
+
+impl RuleSet {
+    /// part 1
+    fn camel() -> RuleSet {
+        RuleSet {
+            ranks: "23456789TJQKA".chars().collect(),
+            suits: vec![],
+            wild: vec![],
+            hand_size: 5,
+            hand_types: HandTypes::ByGroups(TypeOrder::LargestGroup),
+            tie_break: TieBreak::InOrder,
+        }
+    }
+
+    /// part 2: jokers are wild, and the weakest card on their own
+    fn camel_joker() -> RuleSet {
+        RuleSet {
+            ranks: "J23456789TQKA".chars().collect(),
+            wild: vec!['J'],
+            ..RuleSet::camel()
+        }
+    }
+
+    /// five card poker, with cards written like `Ah` or `Tc` (aces only count high)
+    fn poker() -> RuleSet {
+        let straight = HandTypeDef {
+            straight: true,
+            ..HandTypeDef::any("straight")
+        };
+        let flush = HandTypeDef {
+            flush: true,
+            ..HandTypeDef::any("flush")
+        };
+        let straight_flush = HandTypeDef {
+            name: "straight flush".to_string(),
+            flush: true,
+            ..straight.clone()
+        };
+
+        RuleSet {
+            ranks: "23456789TJQKA".chars().collect(),
+            suits: "cdhs".chars().collect(),
+            wild: vec![],
+            hand_size: 5,
+            hand_types: HandTypes::Listed(vec![
+                HandTypeDef::any("high card"),
+                HandTypeDef::groups("one pair", &[2, 1, 1, 1]),
+                HandTypeDef::groups("two pair", &[2, 2, 1]),
+                HandTypeDef::groups("three of a kind", &[3, 1, 1]),
+                straight,
+                flush,
+                HandTypeDef::groups("full house", &[3, 2]),
+                HandTypeDef::groups("four of a kind", &[4, 1]),
+                straight_flush,
+            ]),
+            tie_break: TieBreak::Grouped,
+        }
+    }
 
-    if !hand.contains("J") {
-        return get_type_of_hand(hand);
-    } else {
-        let mut best_hand = get_type_of_hand(hand);
-        for card in "AKQT98765432".split("") {
-            let hand_type = get_type_of_hand(&hand.replace("J", card));
-            if hand_type.partial_cmp(&best_hand).unwrap() == Ordering::Greater {
-                best_hand = hand_type;
+    fn preset(name: &str) -> Option<RuleSet> {
+        match name {
+            "camel" => Some(RuleSet::camel()),
+            "camel-joker" => Some(RuleSet::camel_joker()),
+            "poker" => Some(RuleSet::poker()),
+            _ => None,
+        }
+    }
+
+    /// checks the rule set makes sense
+    fn validate(&self) -> Result<(), String> {
+        if self.ranks.is_empty() || self.ranks.len() > 256 || self.suits.len() > 256 {
+            return Err(
+                "a rule set needs between 1 and 256 ranks, and at most 256 suits".to_string(),
+            );
+        }
+        if self.hand_size == 0 || self.hand_size > 128 {
+            return Err("hands need between 1 and 128 cards".to_string());
+        }
+        if let Some(&chr) = self.wild.iter().find(|chr| !self.ranks.contains(chr)) {
+            return Err(format!("wild card {:?} is not one of the ranks", chr));
+        }
+
+        let defs = match &self.hand_types {
+            HandTypes::Listed(defs) => defs,
+            HandTypes::ByGroups(_) => return Ok(()),
+        };
+        if defs.is_empty() {
+            return Err("a rule set needs at least one hand type".to_string());
+        }
+
+        for def in defs {
+            if let Some(groups) = &def.groups {
+                if groups.iter().sum::<usize>() != self.hand_size {
+                    return Err(format!(
+                        "{} groups {:?} don't add up to {} cards",
+                        def.name, groups, self.hand_size
+                    ));
+                }
+                if def.straight && groups.iter().any(|&size| size > 1) {
+                    return Err(format!("{} can't be a straight and have groups", def.name));
+                }
+            }
+            if def.flush && self.suits.is_empty() {
+                return Err(format!("{} needs suits to be a flush", def.name));
             }
         }
-        return best_hand;
+
+        Ok(())
     }
-}
 
-fn get_type_of_hand(hand: &String) -> HandType {
-    let card_counts = count_each_character(hand);
-    let no_of_unique_cards = card_counts.keys().count();
+    fn is_wild(&self, card: Card) -> bool {
+        self.wild.contains(&self.ranks[card.rank as usize])
+    }
 
-    if no_of_unique_cards == 1 {
-        return HandType::FiveOfAKind;
-    } else if no_of_unique_cards == 2 {
-        if card_counts.values().into_iter().any(|&cnt| cnt == 4) {
-            return HandType::FourOfAKind;
-        } else {
-            return HandType::FullHouse;
+    fn card_string(&self, card: Card) -> String {
+        let mut string = self.ranks[card.rank as usize].to_string();
+        if let Some(suit) = card.suit {
+            string.push(self.suits[suit as usize]);
         }
-    } else if no_of_unique_cards == 3 {
-        if card_counts.values().into_iter().any(|&cnt| cnt == 3) {
-            return HandType::ThreeOfAKind;
-        } else {
-            return HandType::TwoPair;
+        string
+    }
+
+    fn parse_cards(&self, hand: &str) -> Result<Vec<Card>, String> {
+        let mut chars = hand.chars();
+        let mut cards = Vec::with_capacity(self.hand_size);
+
+        while let Some(chr) = chars.next() {
+            let rank = self
+                .ranks
+                .iter()
+                .position(|&rank| rank == chr)
+                .ok_or_else(|| format!("unknown rank {:?}", chr))?;
+            let suit = match self.suits.is_empty() {
+                true => None,
+                false => {
+                    let chr = chars.next().ok_or("the last card has no suit")?;
+                    let suit = self
+                        .suits
+                        .iter()
+                        .position(|&suit| suit == chr)
+                        .ok_or_else(|| format!("unknown suit {:?}", chr))?;
+                    Some(suit as u8)
+                }
+            };
+
+            cards.push(Card {
+                rank: rank as u8,
+                suit,
+            });
         }
-    } else if no_of_unique_cards == 4 {
-        return HandType::OnePair;
-    } else {
-        return HandType::HighCard;
+
+        if cards.len() != self.hand_size {
+            return Err(format!("expected {} cards", self.hand_size));
+        }
+
+        Ok(cards)
     }
-}
 
-fn compare_card(card1: &char, card2: &char, with_joker: bool) -> Ordering {
-    if card1 == card2 {
-        return Ordering::Equal;
+    /// how the cards group with the wild cards joined up with the rest, largest group first.
+    ///
+    /// wild cards always do best joining the largest group of the other cards: that makes the
+    /// largest group as big as it can be without adding a group, and a new group of wild cards
+    /// would be smaller than the one it could have joined
+    fn best_grouping(&self, cards: &[Card]) -> Vec<usize> {
+        let wilds = cards.iter().filter(|&&card| self.is_wild(card)).count();
+        let mut sizes = group_sizes(cards.iter().filter(|&&card| !self.is_wild(card)));
+        match sizes.first_mut() {
+            Some(largest) => *largest += wilds,
+            None => sizes.push(wilds),
+        }
+        sizes
     }
-    let cards = if with_joker {
-        "AKQT98765432J"
-    } else {
-        "AKQJT98765432"
-    };
 
-    for c in cards.chars() {
-        if &c == card1 {
-            // found card1 first
-            return Ordering::Greater;
+    /// lays out the hand types, and checks that the rule set makes sense and that hands fit in a
+    /// `Hand` key
+    fn ranker(&self) -> Result<Ranker<'_>, String> {
+        self.validate()?;
+
+        let groupings = match &self.hand_types {
+            HandTypes::Listed(_) => vec![],
+            HandTypes::ByGroups(order) => {
+                if count_groupings(self.hand_size) > 1 << 20 {
+                    return Err(format!(
+                        "{} cards group in too many ways to rank",
+                        self.hand_size
+                    ));
+                }
+
+                let mut groupings = groupings(self.hand_size);
+                match order {
+                    TypeOrder::LargestGroup => groupings.sort(),
+                    TypeOrder::FewestGroups => {
+                        groupings.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)))
+                    }
+                }
+                groupings
+            }
+        };
+
+        let num_types = match &self.hand_types {
+            HandTypes::Listed(defs) => defs.len(),
+            HandTypes::ByGroups(_) => groupings.len(),
+        };
+        let card_bits = bits_needed(self.ranks.len() - 1);
+        let tie_break_cards = match self.tie_break {
+            TieBreak::None => 0,
+            _ => self.hand_size as u32,
+        };
+        if bits_needed(num_types - 1) + tie_break_cards * card_bits > u128::BITS {
+            return Err(format!(
+                "hands of {} cards don't fit in a key",
+                self.hand_size
+            ));
+        }
+
+        Ok(Ranker {
+            rules: self,
+            grouping_types: groupings
+                .iter()
+                .enumerate()
+                .map(|(idx, grouping)| (grouping.clone(), idx))
+                .collect(),
+            groupings,
+            card_bits,
+        })
+    }
+
+    /// whether the cards that aren't wild can make the hand type, with wild cards making up the
+    /// rest of the hand
+    fn qualifies(&self, def: &HandTypeDef, others: &[Card]) -> bool {
+        if let Some(groups) = &def.groups {
+            // the groups there already have to fit into the wanted groups, biggest into biggest,
+            // with wild cards making up the difference. the sizes add up to the hand size either
+            // way, so fitting in means there are exactly enough wild cards
+            if !fits_groups(&group_sizes(others.iter()), groups) {
+                return false;
+            }
+        }
+
+        if def.flush {
+            let mut suits = others.iter().map(|card| card.suit);
+            if let Some(first) = suits.next() {
+                if suits.any(|suit| suit != first) {
+                    return false;
+                }
+            }
         }
-        if &c == card2 {
-            return Ordering::Less;
+
+        if def.straight {
+            // wild cards can stand in for their own rank too, so straights run over every rank
+            let mut steps = others
+                .iter()
+                .map(|card| card.rank as usize)
+                .collect::<Vec<usize>>();
+            steps.sort_unstable();
+
+            if self.ranks.len() < self.hand_size || steps.windows(2).any(|pair| pair[0] == pair[1])
+            {
+                return false;
+            }
+            if let (Some(low), Some(high)) = (steps.first(), steps.last()) {
+                if high - low >= self.hand_size {
+                    return false;
+                }
+            }
         }
+
+        true
     }
+}
 
-    // should never reach this
-    return card1.cmp(card2);
+/// a rule set with its hand types laid out weakest first, ready to rank hands with
+#[derive(Debug)]
+struct Ranker<'a> {
+    rules: &'a RuleSet,
+    /// for hand types made of groups, every way the cards can group, weakest first
+    groupings: Vec<Vec<usize>>,
+    /// where each grouping is in `groupings`
+    grouping_types: HashMap<Vec<usize>, usize>,
+    /// bits per card in a key
+    card_bits: u32,
 }
 
-fn compare_hands(hand1: &String, hand2: &String, with_joker: bool) -> Ordering {
-    let hand1_type = if with_joker {
-        get_best_hand_with_joker(hand1)
-    } else {
-        get_type_of_hand(hand1)
-    };
-    let hand2_type = if with_joker {
-        get_best_hand_with_joker(hand2)
-    } else {
-        get_type_of_hand(hand2)
-    };
+impl Ranker<'_> {
+    /// the strongest hand type the cards qualify for, with wild cards standing in for whatever
+    /// does best, as an index into the hand types
+    fn hand_type(&self, cards: &[Card]) -> usize {
+        match &self.rules.hand_types {
+            HandTypes::Listed(defs) => {
+                let others = cards
+                    .iter()
+                    .copied()
+                    .filter(|&card| !self.rules.is_wild(card))
+                    .collect::<Vec<Card>>();
+                (0..defs.len())
+                    .rev()
+                    .find(|&idx| self.rules.qualifies(&defs[idx], &others))
+                    .unwrap_or(0)
+            }
+            HandTypes::ByGroups(_) => self.grouping_types[&self.rules.best_grouping(cards)],
+        }
+    }
 
-    if hand1_type == hand2_type {
-        for (idx, c) in hand1.char_indices() {
-            let comparison = compare_card(&c, &hand2.chars().nth(idx).unwrap(), with_joker);
-            if comparison != Ordering::Equal {
-                return comparison;
+    fn type_name(&self, hand_type: usize) -> String {
+        match &self.rules.hand_types {
+            HandTypes::Listed(defs) => defs[hand_type].name.clone(),
+            HandTypes::ByGroups(_) => grouping_name(&self.groupings[hand_type]),
+        }
+    }
+
+    /// the cards with every wild card replaced by what it stands in for, picking the strongest
+    /// card that still makes the hand's type. a wild card can stand in for its own rank, but with
+    /// suits it can't be a card that's already in the hand. `None` if there are no wild cards in
+    /// the hand, or if the type can only be made by repeating a card
+    fn substitution(&self, cards: &[Card], hand_type: usize) -> Option<Vec<Card>> {
+        let rules = self.rules;
+        if !cards.iter().any(|&card| rules.is_wild(card)) {
+            return None;
+        }
+
+        let mut others = cards
+            .iter()
+            .copied()
+            .filter(|&card| !rules.is_wild(card))
+            .collect::<Vec<Card>>();
+
+        let makes_type: CardsCheck = match &rules.hand_types {
+            HandTypes::Listed(defs) => {
+                let def = &defs[hand_type];
+                Box::new(move |others| rules.qualifies(def, others))
             }
+            HandTypes::ByGroups(_) => {
+                let groups = &self.groupings[hand_type];
+                Box::new(move |others| fits_groups(&group_sizes(others.iter()), groups))
+            }
+        };
+
+        let suits = match rules.suits.len() {
+            0 => vec![None],
+            num_suits => (0..num_suits as u8).rev().map(Some).collect(),
+        };
+        let candidates = (0..rules.ranks.len() as u8)
+            .rev()
+            .flat_map(|rank| suits.iter().map(move |&suit| Card { rank, suit }))
+            .collect::<Vec<Card>>();
+        let has_suits = !rules.suits.is_empty();
+
+        let mut substituted = cards.to_vec();
+        for card in substituted.iter_mut() {
+            if !rules.is_wild(*card) {
+                continue;
+            }
+            // a partly substituted hand that can still make the type can always be finished, so
+            // the first card that keeps it possible never needs undoing
+            let stand_in = candidates.iter().copied().find(|&candidate| {
+                if has_suits && others.contains(&candidate) {
+                    return false;
+                }
+                others.push(candidate);
+                let possible = makes_type(&others);
+                others.pop();
+                possible
+            })?;
+            others.push(stand_in);
+            *card = stand_in;
+        }
+
+        Some(substituted)
+    }
+
+    fn parse_hand(&self, hand: &str) -> Result<Hand, String> {
+        let cards = self
+            .rules
+            .parse_cards(hand)
+            .map_err(|e| format!("Error parsing hand {}: {}", hand, e))?;
+        let hand_type = self.hand_type(&cards);
+
+        let push = |key: u128, card: &Card| key << self.card_bits | card.rank as u128;
+        let key = match self.rules.tie_break {
+            TieBreak::InOrder => cards.iter().fold(hand_type as u128, push),
+            TieBreak::Sorted => {
+                let mut ordered = cards.clone();
+                ordered.sort_unstable_by_key(|card| Reverse(card.rank));
+                ordered.iter().fold(hand_type as u128, push)
+            }
+            TieBreak::Grouped => {
+                let count = |card: &Card| cards.iter().filter(|c| c.rank == card.rank).count();
+                let mut ordered = cards.clone();
+                ordered.sort_by_key(|card| Reverse((count(card), card.rank)));
+                ordered.iter().fold(hand_type as u128, push)
+            }
+            TieBreak::None => hand_type as u128,
+        };
+
+        Ok(Hand {
+            cards,
+            hand_type,
+            key,
+        })
+    }
+}
+
+/// a test a set of cards passes or fails
+type CardsCheck<'a> = Box<dyn Fn(&[Card]) -> bool + 'a>;
+
+/// a hand parsed once, with its type worked out up front and everything needed to order it
+/// packed into `key`. hands are sorted by that key
+#[derive(Debug, Clone)]
+struct Hand {
+    cards: Vec<Card>,
+    /// index into the rule set's hand types
+    hand_type: usize,
+    /// the hand type in the top bits, then a fixed number of bits per card in tie-break order, so
+    /// comparing keys compares type first and then card by card
+    key: u128,
+}
+
+/// how many bits it takes to write `n`
+fn bits_needed(n: usize) -> u32 {
+    usize::BITS - n.leading_zeros()
+}
+
+/// how many of each rank there are, largest group first
+fn group_sizes<'a>(cards: impl Iterator<Item = &'a Card> + Clone) -> Vec<usize> {
+    // hands have at most 128 cards and rule sets at most 256 ranks
+    let mut card_counts = [0_u8; 256];
+    for card in cards.clone() {
+        card_counts[card.rank as usize] += 1;
+    }
+
+    let mut sizes = vec![];
+    for card in cards {
+        let count = &mut card_counts[card.rank as usize];
+        if *count > 0 {
+            sizes.push(*count as usize);
+            *count = 0;
+        }
+    }
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    sizes
+}
+
+/// whether cards grouped as `sizes` can be made up into `groups` by adding cards, both largest
+/// group first
+fn fits_groups(sizes: &[usize], groups: &[usize]) -> bool {
+    sizes.len() <= groups.len() && sizes.iter().zip(groups).all(|(have, want)| have <= want)
+}
+
+/// how many ways `n` cards can group
+fn count_groupings(n: usize) -> u128 {
+    let mut counts = vec![0_u128; n + 1];
+    counts[0] = 1;
+    for size in 1..=n {
+        for total in size..=n {
+            counts[total] = counts[total].saturating_add(counts[total - size]);
         }
-        return Ordering::Equal;
-    } else {
-        hand1_type.partial_cmp(&hand2_type).unwrap()
     }
+    counts[n]
 }
 
-fn part1(hand_bid_pairs: &Vec<(String, u64)>) -> u64 {
-    let mut hand_bid_pairs = hand_bid_pairs.clone();
-    hand_bid_pairs.sort_by(|a, b| compare_hands(&a.0, &b.0, false));
+/// every way `n` cards can group, each largest group first
+fn groupings(n: usize) -> Vec<Vec<usize>> {
+    fn extend(left: usize, largest: usize, prefix: &mut Vec<usize>, all: &mut Vec<Vec<usize>>) {
+        if left == 0 {
+            all.push(prefix.clone());
+            return;
+        }
+        for size in 1..=left.min(largest) {
+            prefix.push(size);
+            extend(left - size, size, prefix, all);
+            prefix.pop();
+        }
+    }
+
+    let mut all = vec![];
+    extend(n, n, &mut vec![], &mut all);
+    all
+}
+
+/// what a hand that groups as `sizes` (largest group first) is called
+fn grouping_name(sizes: &[usize]) -> String {
+    let all_single = |rest: &[usize]| rest.iter().all(|&size| size == 1);
+    match sizes {
+        [1, ..] => "high card".to_string(),
+        [2, 2, rest @ ..] if all_single(rest) => "two pair".to_string(),
+        [3, 2] => "full house".to_string(),
+        [2, rest @ ..] if all_single(rest) => "one pair".to_string(),
+        [3, rest @ ..] if all_single(rest) => "three of a kind".to_string(),
+        [4, rest @ ..] if all_single(rest) => "four of a kind".to_string(),
+        [5, rest @ ..] if all_single(rest) => "five of a kind".to_string(),
+        [n, rest @ ..] if all_single(rest) => format!("{} of a kind", n),
+        _ => sizes
+            .iter()
+            .map(|size| size.to_string())
+            .collect::<Vec<String>>()
+            .join("+"),
+    }
+}
+
+/// a hand with where it finished
+#[derive(Debug)]
+struct Ranked {
+    /// position in the input, from 1
+    position: usize,
+    hand: String,
+    hand_type: String,
+    /// the hand with its wild cards substituted, if it has any
+    substitution: Option<String>,
+    rank: u64,
+    bid: u64,
+    winnings: u64,
+}
 
-    hand_bid_pairs
+/// every hand with its rank, weakest first
+fn rank_hands(hand_bid_pairs: &[(String, u64)], rules: &RuleSet) -> Result<Vec<Ranked>, String> {
+    let ranker = rules.ranker()?;
+    let mut hands = hand_bid_pairs
         .iter()
         .enumerate()
-        .map(|(idx, pair)| pair.1 * (idx as u64 + 1))
-        .sum()
+        .map(|(idx, (hand, bid))| Ok((idx, ranker.parse_hand(hand)?, *bid)))
+        .collect::<Result<Vec<(usize, Hand, u64)>, String>>()?;
+    // stable, so hands that tie outright keep their order
+    hands.sort_by_key(|(_, hand, _)| hand.key);
+
+    Ok(hands
+        .into_iter()
+        .enumerate()
+        .map(|(rank_idx, (idx, hand, bid))| {
+            let rank = rank_idx as u64 + 1;
+            Ranked {
+                position: idx + 1,
+                hand: hand_bid_pairs[idx].0.clone(),
+                hand_type: ranker.type_name(hand.hand_type),
+                substitution: ranker
+                    .substitution(&hand.cards, hand.hand_type)
+                    .map(|cards| {
+                        cards
+                            .iter()
+                            .map(|&card| rules.card_string(card))
+                            .collect::<String>()
+                    }),
+                rank,
+                bid,
+                winnings: bid * rank,
+            }
+        })
+        .collect())
 }
 
-fn part2(hand_bid_pairs: &Vec<(String, u64)>) -> u64 {
-    let mut hand_bid_pairs = hand_bid_pairs.clone();
-    hand_bid_pairs.sort_by(|a, b| compare_hands(&a.0, &b.0, true));
+fn total_winnings(hand_bid_pairs: &[(String, u64)], rules: &RuleSet) -> Result<u64, String> {
+    let ranker = rules.ranker()?;
+    let mut hands = hand_bid_pairs
+        .iter()
+        .map(|(hand, bid)| Ok((ranker.parse_hand(hand)?, *bid)))
+        .collect::<Result<Vec<(Hand, u64)>, String>>()?;
+    // stable, so hands that tie outright keep their order, the same as in `rank_hands`
+    hands.sort_by_key(|(hand, _)| hand.key);
 
-    hand_bid_pairs
+    Ok(hands
         .iter()
         .enumerate()
-        .map(|(idx, pair)| pair.1 * (idx as u64 + 1))
-        .sum()
+        .map(|(idx, (_, bid))| bid * (idx as u64 + 1))
+        .sum())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReportFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+/// what the report is sorted by. everything sorts smallest first, ties in rank order
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum SortBy {
+    #[default]
+    Rank,
+    Input,
+    Bid,
+    Winnings,
+}
+
+/// per hand: the cards, the type they resolved to, what the wild cards stood in for, and the rank,
+/// bid and winnings that came of it
+fn report(ranked: &mut [Ranked], sort_by: SortBy, format: ReportFormat) -> String {
+    match sort_by {
+        SortBy::Rank => ranked.sort_by_key(|r| r.rank),
+        SortBy::Input => ranked.sort_by_key(|r| r.position),
+        SortBy::Bid => ranked.sort_by_key(|r| (r.bid, r.rank)),
+        SortBy::Winnings => ranked.sort_by_key(|r| (r.winnings, r.rank)),
+    }
+
+    let mut lines = vec![];
+
+    match format {
+        ReportFormat::Table => lines.push(format!(
+            "{:>6}  {:<12}  {:<16}  {:<12}  {:>6}  {:>8}  {:>10}",
+            "line", "hand", "type", "substituted", "rank", "bid", "winnings"
+        )),
+        ReportFormat::Csv => {
+            lines.push("line,hand,type,substituted,rank,bid,winnings".to_string());
+        }
+        ReportFormat::Json => {}
+    }
+
+    for r in ranked.iter() {
+        lines.push(match format {
+            ReportFormat::Table => format!(
+                "{:>6}  {:<12}  {:<16}  {:<12}  {:>6}  {:>8}  {:>10}",
+                r.position,
+                r.hand,
+                r.hand_type,
+                r.substitution.as_deref().unwrap_or("-"),
+                r.rank,
+                r.bid,
+                r.winnings
+            ),
+            ReportFormat::Csv => format!(
+                "{},{},{},{},{},{},{}",
+                r.position,
+                csv_field(&r.hand),
+                csv_field(&r.hand_type),
+                csv_field(r.substitution.as_deref().unwrap_or("")),
+                r.rank,
+                r.bid,
+                r.winnings
+            ),
+            ReportFormat::Json => format!(
+                "  {{\"line\": {}, \"hand\": {}, \"type\": {}, \"substituted\": {}, \"rank\": {}, \"bid\": {}, \"winnings\": {}}}",
+                r.position,
+                json_string(&r.hand),
+                json_string(&r.hand_type),
+                r.substitution
+                    .as_deref()
+                    .map_or("null".to_string(), json_string),
+                r.rank,
+                r.bid,
+                r.winnings
+            ),
+        });
+    }
+
+    match format {
+        ReportFormat::Table => {
+            let total = ranked.iter().map(|r| r.winnings).sum::<u64>();
+            lines.push(format!("total winnings: {}", total));
+            lines.join("\n")
+        }
+        ReportFormat::Csv => lines.join("\n"),
+        ReportFormat::Json => format!("[\n{}\n]", lines.join(",\n")),
+    }
+}
+
+fn part1(hand_bid_pairs: &[(String, u64)]) -> Result<u64, String> {
+    total_winnings(hand_bid_pairs, &RuleSet::camel())
+}
+
+fn part2(hand_bid_pairs: &[(String, u64)]) -> Result<u64, String> {
+    total_winnings(hand_bid_pairs, &RuleSet::camel_joker())
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    input: Option<String>,
+    /// when set, only these rules are played instead of both parts
+    rules: Option<RuleSet>,
+    report: Option<ReportFormat>,
+    sort_by: SortBy,
+}
+
+/// reads `--input <path>`, `--rules <camel | camel-joker | poker>`, `--wild <ranks>`,
+/// `--tie-break <in-order | sorted | grouped | none>`, `--hand-size <n>` and
+/// `--type-order <largest-group | fewest-groups>` from the command line. everything after
+/// `--rules` adjusts the rules it picked (part 1's if none are). `--type-order` replaces any listed
+/// hand types with one type per way of grouping the cards. `--report <table | csv | json>` prints
+/// every hand's ranking under those rules, sorted by `--sort <rank | input | bid | winnings>`
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                options.input = Some(args.next().ok_or("--input requires a path")?.clone());
+            }
+            "--rules" => {
+                let name = args.next().ok_or("--rules requires a preset")?;
+                options.rules =
+                    Some(RuleSet::preset(name).ok_or_else(|| format!("unknown rules `{}`", name))?);
+            }
+            "--wild" => {
+                let wild = args.next().ok_or("--wild requires the wild ranks")?;
+                options.rules.get_or_insert_with(RuleSet::camel).wild = wild.chars().collect();
+            }
+            "--tie-break" => {
+                options.rules.get_or_insert_with(RuleSet::camel).tie_break =
+                    match args.next().map(|s| s.as_str()) {
+                        Some("in-order") => TieBreak::InOrder,
+                        Some("sorted") => TieBreak::Sorted,
+                        Some("grouped") => TieBreak::Grouped,
+                        Some("none") => TieBreak::None,
+                        Some(other) => return Err(format!("unknown tie-break `{}`", other)),
+                        None => return Err("--tie-break requires a strategy".to_string()),
+                    };
+            }
+            "--report" => {
+                options.report = Some(match args.next().map(|s| s.as_str()) {
+                    Some("table") => ReportFormat::Table,
+                    Some("csv") => ReportFormat::Csv,
+                    Some("json") => ReportFormat::Json,
+                    Some(other) => return Err(format!("unknown report format `{}`", other)),
+                    None => return Err("--report requires a format".to_string()),
+                });
+            }
+            "--sort" => {
+                options.sort_by = match args.next().map(|s| s.as_str()) {
+                    Some("rank") => SortBy::Rank,
+                    Some("input") => SortBy::Input,
+                    Some("bid") => SortBy::Bid,
+                    Some("winnings") => SortBy::Winnings,
+                    Some(other) => return Err(format!("unknown sort `{}`", other)),
+                    None => return Err("--sort requires a field".to_string()),
+                };
+            }
+            "--hand-size" => {
+                let size = args.next().ok_or("--hand-size requires a number")?;
+                options.rules.get_or_insert_with(RuleSet::camel).hand_size = size
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid hand size `{}`", size))?;
+            }
+            "--type-order" => {
+                options.rules.get_or_insert_with(RuleSet::camel).hand_types =
+                    HandTypes::ByGroups(match args.next().map(|s| s.as_str()) {
+                        Some("largest-group") => TypeOrder::LargestGroup,
+                        Some("fewest-groups") => TypeOrder::FewestGroups,
+                        Some(other) => return Err(format!("unknown type order `{}`", other)),
+                        None => return Err("--type-order requires an order".to_string()),
+                    });
+            }
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        }
+    }
+
+    if let Some(rules) = &options.rules {
+        rules.ranker()?;
+    }
+
+    Ok(options)
 }
 
 pub fn main() {
-    let hand_bid_pairs = String::from_utf8(include_bytes!("sample_input_data/day7.txt").to_vec())
-        .unwrap()
-        .split("\n")
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    });
+
+    let input = match &options.input {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("error: could not read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => String::from_utf8(include_bytes!("sample_input_data/day7.txt").to_vec()).unwrap(),
+    };
+
+    let hand_bid_pairs = input
+        .lines()
+        .filter(|line| !line.trim().is_empty())
         .map(|line| {
             let mut line_split = line.split_whitespace();
 
@@ -153,6 +866,137 @@ pub fn main() {
         })
         .collect::<Vec<(String, u64)>>();
 
-    println!("{}", part1(&hand_bid_pairs));
-    println!("{}", part2(&hand_bid_pairs));
+    if let Some(format) = options.report {
+        let rules = options.rules.unwrap_or_else(RuleSet::camel);
+        match rank_hands(&hand_bid_pairs, &rules) {
+            Ok(mut ranked) => println!("{}", report(&mut ranked, options.sort_by, format)),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let results = match &options.rules {
+        Some(rules) => vec![total_winnings(&hand_bid_pairs, rules)],
+        None => vec![part1(&hand_bid_pairs), part2(&hand_bid_pairs)],
+    };
+
+    for result in results {
+        match result {
+            Ok(total) => println!("{}", total),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hand_bid_pairs(input: &str) -> Vec<(String, u64)> {
+        input
+            .lines()
+            .map(|line| {
+                let (hand, bid) = line.split_once(' ').unwrap();
+                (hand.to_string(), bid.parse::<u64>().unwrap())
+            })
+            .collect()
+    }
+
+    /// the best hand type with wild cards found by trying every other rank in their place.
+    /// `without_wild` ranks with the same rules, but with nothing wild
+    fn hand_type_by_search(rules: &RuleSet, without_wild: &Ranker, cards: &[Card]) -> usize {
+        (0..rules.ranks.len() as u8)
+            .filter(|&rank| !rules.is_wild(Card { rank, suit: None }))
+            .map(|rank| {
+                let replaced = cards
+                    .iter()
+                    .map(|&card| {
+                        if rules.is_wild(card) {
+                            Card { rank, suit: None }
+                        } else {
+                            card
+                        }
+                    })
+                    .collect::<Vec<Card>>();
+                without_wild.hand_type(&replaced)
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn sample() {
+        let pairs = hand_bid_pairs(include_str!("sample_input_data/day7.txt"));
+        assert_eq!(part1(&pairs), Ok(6440));
+        assert_eq!(part2(&pairs), Ok(5905));
+    }
+
+    #[test]
+    fn poker_sample() {
+        let pairs = hand_bid_pairs(include_str!("sample_input_data/day7_poker.txt"));
+        assert_eq!(total_winnings(&pairs, &RuleSet::poker()), Ok(930));
+    }
+
+    #[test]
+    fn wild_cards_stand_in_for_real_cards() {
+        let rules = RuleSet {
+            wild: vec!['J'],
+            ..RuleSet::poker()
+        };
+        let ranker = rules.ranker().unwrap();
+
+        for (hand, hand_type, substituted) in [
+            // the jack stands in for itself to finish the straight
+            ("AhKhQhJhTh", "straight flush", "AhKhQhJhTh"),
+            // the ace of spades is already in the hand
+            ("AsAdQcJd2h", "three of a kind", "AsAdQcAh2h"),
+        ] {
+            let parsed = ranker.parse_hand(hand).unwrap();
+            let cards = ranker
+                .substitution(&parsed.cards, parsed.hand_type)
+                .unwrap();
+            assert_eq!(ranker.type_name(parsed.hand_type), hand_type);
+            assert_eq!(
+                cards
+                    .iter()
+                    .map(|&card| rules.card_string(card))
+                    .collect::<String>(),
+                substituted
+            );
+        }
+    }
+
+    #[test]
+    fn jokers_match_exhaustive_search() {
+        let rules = RuleSet::camel_joker();
+        let rules_without_wild = RuleSet {
+            wild: vec![],
+            ..rules.clone()
+        };
+        let ranker = rules.ranker().unwrap();
+        let without_wild = rules_without_wild.ranker().unwrap();
+        for n in 0..13_u32.pow(5) {
+            let cards = (0..5)
+                .map(|i| Card {
+                    rank: (n / 13_u32.pow(i) % 13) as u8,
+                    suit: None,
+                })
+                .collect::<Vec<Card>>();
+            assert_eq!(
+                ranker.hand_type(&cards),
+                hand_type_by_search(&rules, &without_wild, &cards),
+                "{}",
+                cards
+                    .iter()
+                    .map(|&card| rules.card_string(card))
+                    .collect::<String>()
+            );
+        }
+    }
 }
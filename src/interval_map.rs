@@ -0,0 +1,387 @@
+use std::{fmt, ops::Range};
+
+use crate::range_set::RangeSet;
+
+/// a piece of an [`IntervalMap`]: every value in `source` is moved to the same position in the
+/// range starting at `dest_start`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub source: Range<u64>,
+    pub dest_start: u64,
+}
+
+impl Segment {
+    pub fn dest(&self) -> Range<u64> {
+        self.dest_start..self.dest_start + (self.source.end - self.source.start)
+    }
+
+    fn map(&self, value: u64) -> u64 {
+        self.dest_start + (value - self.source.start)
+    }
+}
+
+/// a piecewise function over `u64`, made of sorted, non-overlapping segments that each shift their
+/// values by a fixed offset. anything not covered by a segment maps to itself.
+///
+/// ranges are half open, so the domain is `0..u64::MAX`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalMap {
+    segments: Vec<Segment>,
+}
+
+impl IntervalMap {
+    pub fn new() -> IntervalMap {
+        IntervalMap::default()
+    }
+
+    /// builds a map from `(source range, destination start)` pairs. empty ranges are skipped;
+    /// overlapping source ranges, or destinations that don't fit in a `u64`, are an error
+    pub fn from_segments(
+        segments: impl IntoIterator<Item = (Range<u64>, u64)>,
+    ) -> Result<IntervalMap, String> {
+        let mut segments = segments
+            .into_iter()
+            .filter(|(source, _)| !source.is_empty())
+            .map(|(source, dest_start)| Segment { source, dest_start })
+            .collect::<Vec<Segment>>();
+
+        segments.sort_by_key(|segment| segment.source.start);
+
+        for segment in &segments {
+            if segment
+                .dest_start
+                .checked_add(segment.source.end - segment.source.start)
+                .is_none()
+            {
+                return Err(format!(
+                    "segment {:?} -> {}.. goes past u64::MAX",
+                    segment.source, segment.dest_start
+                ));
+            }
+        }
+
+        for pair in segments.windows(2) {
+            if pair[1].source.start < pair[0].source.end {
+                return Err(format!(
+                    "segments {:?} and {:?} overlap",
+                    pair[0].source, pair[1].source
+                ));
+            }
+        }
+
+        Ok(IntervalMap { segments })
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// the segment containing `value`, if any
+    fn segment_at(&self, value: u64) -> Option<&Segment> {
+        // index of the first segment starting after value, so the one before it is the only
+        // candidate
+        let idx = self
+            .segments
+            .partition_point(|segment| segment.source.start <= value);
+
+        idx.checked_sub(1)
+            .map(|idx| &self.segments[idx])
+            .filter(|segment| segment.source.contains(&value))
+    }
+
+    pub fn map(&self, value: u64) -> u64 {
+        match self.segment_at(value) {
+            Some(segment) => segment.map(value),
+            None => value,
+        }
+    }
+
+    /// splits `range` at segment boundaries, and returns each piece as
+    /// `(source piece, mapped piece)`, in source order
+    pub fn map_pieces(&self, range: Range<u64>) -> Vec<(Range<u64>, Range<u64>)> {
+        let mut pieces = vec![];
+        let mut start = range.start;
+
+        let first = self
+            .segments
+            .partition_point(|segment| segment.source.end <= range.start);
+
+        for segment in &self.segments[first..] {
+            if start >= range.end || segment.source.start >= range.end {
+                break;
+            }
+
+            if start < segment.source.start {
+                // gap before the segment, which maps to itself
+                pieces.push((start..segment.source.start, start..segment.source.start));
+                start = segment.source.start;
+            }
+
+            let end = range.end.min(segment.source.end);
+            pieces.push((
+                start..end,
+                segment.map(start)..segment.map(start) + (end - start),
+            ));
+            start = end;
+        }
+
+        if start < range.end {
+            pieces.push((start..range.end, start..range.end));
+        }
+
+        pieces
+    }
+
+    /// the image of `range`: one output range per piece of `range` between segment boundaries,
+    /// in the order of their source values
+    pub fn map_range(&self, range: Range<u64>) -> Vec<Range<u64>> {
+        self.map_pieces(range)
+            .into_iter()
+            .map(|(_, dest)| dest)
+            .collect()
+    }
+
+    /// the image of every value in `set`
+    pub fn map_set(&self, set: &RangeSet) -> RangeSet {
+        set.ranges()
+            .iter()
+            .flat_map(|range| self.map_range(range.clone()))
+            .collect()
+    }
+
+    /// every value that maps into `set`
+    pub fn inverse_set(&self, set: &RangeSet) -> RangeSet {
+        set.ranges()
+            .iter()
+            .flat_map(|range| self.inverse_range(range.clone()))
+            .collect()
+    }
+
+    /// every value that maps to `value`, smallest first
+    pub fn inverse(&self, value: u64) -> Vec<u64> {
+        let mut sources = self
+            .segments
+            .iter()
+            .filter(|segment| segment.dest().contains(&value))
+            .map(|segment| segment.source.start + (value - segment.dest_start))
+            .collect::<Vec<u64>>();
+
+        if self.segment_at(value).is_none() {
+            // not covered by any segment, so it maps to itself
+            sources.push(value);
+        }
+
+        sources.sort_unstable();
+        sources
+    }
+
+    /// every range of values that maps into `range`, sorted by start
+    pub fn inverse_range(&self, range: Range<u64>) -> Vec<Range<u64>> {
+        let mut sources = vec![];
+
+        for segment in &self.segments {
+            let dest = segment.dest();
+            let start = range.start.max(dest.start);
+            let end = range.end.min(dest.end);
+
+            if start < end {
+                let source_start = segment.source.start + (start - dest.start);
+                sources.push(source_start..source_start + (end - start));
+            }
+        }
+
+        // the parts of `range` that aren't covered by a segment map to themselves
+        for (source, dest) in self.map_pieces(range) {
+            if source == dest && self.segment_at(source.start).is_none() {
+                sources.push(source);
+            }
+        }
+
+        sources.sort_by_key(|source| (source.start, source.end));
+        sources
+    }
+
+    /// the map that applies `self` and then `next`, as a single piecewise function
+    pub fn then(&self, next: &IntervalMap) -> IntervalMap {
+        let mut segments: Vec<Segment> = vec![];
+
+        for (source, mid) in self.map_pieces(0..u64::MAX) {
+            for (mid_piece, dest) in next.map_pieces(mid.clone()) {
+                let start = source.start + (mid_piece.start - mid.start);
+                let piece = Segment {
+                    source: start..start + (mid_piece.end - mid_piece.start),
+                    dest_start: dest.start,
+                };
+
+                if piece.source.start == piece.dest_start {
+                    // maps to itself, which is what happens outside of segments anyway
+                    continue;
+                }
+
+                match segments.last_mut() {
+                    // carries straight on from the previous segment, with the same offset
+                    Some(last)
+                        if last.source.end == piece.source.start
+                            && last.dest().end == piece.dest_start =>
+                    {
+                        last.source.end = piece.source.end;
+                    }
+                    _ => segments.push(piece),
+                }
+            }
+        }
+
+        IntervalMap { segments }
+    }
+}
+
+impl fmt::Display for IntervalMap {
+    /// one segment per line, as `source range -> destination range (offset)`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.segments.is_empty() {
+            return writeln!(f, "identity");
+        }
+
+        for segment in &self.segments {
+            let dest = segment.dest();
+            let offset = segment.dest_start as i128 - segment.source.start as i128;
+            writeln!(
+                f,
+                "{}..{} -> {}..{} ({:+})",
+                segment.source.start, segment.source.end, dest.start, dest.end, offset
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+
+    const DOMAIN: u64 = 200;
+    const CASES: usize = 500;
+
+    /// where the segments send `value`, worked out one segment at a time
+    fn brute(segments: &[(Range<u64>, u64)], value: u64) -> u64 {
+        segments
+            .iter()
+            .find(|(source, _)| source.contains(&value))
+            .map_or(value, |(source, dest_start)| {
+                dest_start + value - source.start
+            })
+    }
+
+    fn sorted(ranges: Vec<Range<u64>>) -> Vec<u64> {
+        let mut values = ranges.into_iter().flatten().collect::<Vec<u64>>();
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn rejects_overlapping_segments() {
+        assert!(IntervalMap::from_segments([(0..10, 50), (5..15, 80)]).is_err());
+        assert!(IntervalMap::from_segments([(0..10, 50), (10..15, 80)]).is_ok());
+    }
+
+    #[test]
+    fn rejects_segments_past_u64_max() {
+        assert!(IntervalMap::from_segments([(0..10, u64::MAX - 5)]).is_err());
+        assert!(IntervalMap::from_segments([(0..10, u64::MAX - 10)]).is_ok());
+    }
+
+    #[test]
+    fn map_matches_brute_force() {
+        let mut rng = Rng::new();
+        for _ in 0..CASES {
+            let segments = rng.segments();
+            let map = IntervalMap::from_segments(segments.clone()).unwrap();
+            for value in 0..DOMAIN {
+                assert_eq!(map.map(value), brute(&segments, value), "{:?}", segments);
+            }
+        }
+    }
+
+    #[test]
+    fn map_range_matches_brute_force() {
+        let mut rng = Rng::new();
+        for _ in 0..CASES {
+            let segments = rng.segments();
+            let map = IntervalMap::from_segments(segments.clone()).unwrap();
+            let range = rng.range(DOMAIN);
+
+            let mut expected = range
+                .clone()
+                .map(|v| brute(&segments, v))
+                .collect::<Vec<u64>>();
+            expected.sort_unstable();
+            assert_eq!(
+                sorted(map.map_range(range.clone())),
+                expected,
+                "{:?} over {:?}",
+                segments,
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn inverse_matches_brute_force() {
+        let mut rng = Rng::new();
+        for _ in 0..CASES {
+            let segments = rng.segments();
+            let map = IntervalMap::from_segments(segments.clone()).unwrap();
+            for value in 0..DOMAIN {
+                let expected = (0..DOMAIN)
+                    .filter(|&x| brute(&segments, x) == value)
+                    .collect::<Vec<u64>>();
+                assert_eq!(map.inverse(value), expected, "{:?} at {}", segments, value);
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_range_matches_brute_force() {
+        let mut rng = Rng::new();
+        for _ in 0..CASES {
+            let segments = rng.segments();
+            let map = IntervalMap::from_segments(segments.clone()).unwrap();
+            let range = rng.range(DOMAIN);
+
+            let expected = (0..DOMAIN)
+                .filter(|&x| range.contains(&brute(&segments, x)))
+                .collect::<Vec<u64>>();
+            assert_eq!(
+                sorted(map.inverse_range(range.clone())),
+                expected,
+                "{:?} over {:?}",
+                segments,
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn then_matches_mapping_twice() {
+        let mut rng = Rng::new();
+        for _ in 0..CASES {
+            let first = rng.segments();
+            let second = rng.segments();
+            let map = IntervalMap::from_segments(first.clone()).unwrap();
+            let next = IntervalMap::from_segments(second.clone()).unwrap();
+            let composed = map.then(&next);
+
+            for value in 0..DOMAIN {
+                assert_eq!(
+                    composed.map(value),
+                    next.map(brute(&first, value)),
+                    "{:?} then {:?}",
+                    first,
+                    second
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod escape;
+pub mod interval_map;
+pub mod range_set;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,249 @@
+use std::ops::Range;
+
+/// a set of `u64`s stored as sorted ranges. ranges are kept coalesced: none of them overlap or
+/// touch, and none are empty, so two sets with the same values always have the same ranges.
+///
+/// ranges are half open, so like [`IntervalMap`](crate::interval_map::IntervalMap) the domain is
+/// `0..u64::MAX`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RangeSet {
+    ranges: Vec<Range<u64>>,
+}
+
+impl RangeSet {
+    pub fn new() -> RangeSet {
+        RangeSet::default()
+    }
+
+    /// a set holding a single value
+    pub fn point(value: u64) -> RangeSet {
+        let mut set = RangeSet::new();
+        set.insert_point(value);
+        set
+    }
+
+    pub fn ranges(&self) -> &[Range<u64>] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// how many values are in the set
+    pub fn len(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        self.ranges.first().map(|range| range.start)
+    }
+
+    pub fn contains(&self, value: u64) -> bool {
+        let idx = self.ranges.partition_point(|range| range.end <= value);
+        self.ranges
+            .get(idx)
+            .is_some_and(|range| range.contains(&value))
+    }
+
+    pub fn insert_point(&mut self, value: u64) {
+        self.insert(value..value + 1);
+    }
+
+    pub fn insert(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+
+        // every range from `first` up to (not including) `last` overlaps or touches `range`
+        let first = self.ranges.partition_point(|r| r.end < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.end);
+
+        let mut merged = range;
+        if first < last {
+            merged.start = merged.start.min(self.ranges[first].start);
+            merged.end = merged.end.max(self.ranges[last - 1].end);
+        }
+
+        self.ranges.splice(first..last, [merged]);
+    }
+
+    pub fn union(&self, other: &RangeSet) -> RangeSet {
+        let mut union = self.clone();
+        for range in &other.ranges {
+            union.insert(range.clone());
+        }
+        union
+    }
+
+    pub fn intersection(&self, other: &RangeSet) -> RangeSet {
+        let mut ranges = vec![];
+        let (mut i, mut j) = (0, 0);
+
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let a = &self.ranges[i];
+            let b = &other.ranges[j];
+
+            let start = a.start.max(b.start);
+            let end = a.end.min(b.end);
+            if start < end {
+                ranges.push(start..end);
+            }
+
+            // move past whichever range ends first, the other one might still overlap the next
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        // pieces of coalesced ranges never touch each other, so this is coalesced already
+        RangeSet { ranges }
+    }
+
+    /// the values in `self` that aren't in `other`
+    pub fn difference(&self, other: &RangeSet) -> RangeSet {
+        let mut ranges = vec![];
+        let mut j = 0;
+
+        for range in &self.ranges {
+            let mut start = range.start;
+
+            // skip the ranges of `other` that end before this one starts
+            while j < other.ranges.len() && other.ranges[j].end <= start {
+                j += 1;
+            }
+
+            let mut k = j;
+            while k < other.ranges.len() && other.ranges[k].start < range.end {
+                if other.ranges[k].start > start {
+                    ranges.push(start..other.ranges[k].start);
+                }
+                start = start.max(other.ranges[k].end);
+                k += 1;
+            }
+
+            if start < range.end {
+                ranges.push(start..range.end);
+            }
+        }
+
+        RangeSet { ranges }
+    }
+}
+
+impl FromIterator<Range<u64>> for RangeSet {
+    fn from_iter<I: IntoIterator<Item = Range<u64>>>(iter: I) -> RangeSet {
+        let mut ranges = iter
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .collect::<Vec<Range<u64>>>();
+        ranges.sort_by_key(|range| range.start);
+
+        // coalesce in one pass now that they're sorted
+        let mut coalesced: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match coalesced.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => coalesced.push(range),
+            }
+        }
+
+        RangeSet { ranges: coalesced }
+    }
+}
+
+impl FromIterator<u64> for RangeSet {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> RangeSet {
+        iter.into_iter().map(|value| value..value + 1).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+
+    const DOMAIN: u64 = 200;
+    const CASES: usize = 500;
+
+    fn random_set(rng: &mut Rng) -> RangeSet {
+        (0..rng.next(6)).map(|_| rng.range(DOMAIN)).collect()
+    }
+
+    fn in_set(set: &RangeSet, value: u64) -> bool {
+        set.ranges().iter().any(|range| range.contains(&value))
+    }
+
+    /// runs `op` on random pairs of sets and compares the result with `keep` applied to every
+    /// value. comparing whole sets also checks that the result is coalesced
+    fn check_against_points(
+        op: impl Fn(&RangeSet, &RangeSet) -> RangeSet,
+        keep: impl Fn(bool, bool) -> bool,
+    ) {
+        let mut rng = Rng::new();
+        for _ in 0..CASES {
+            let a = random_set(&mut rng);
+            let b = random_set(&mut rng);
+            let expected = (0..DOMAIN)
+                .filter(|&v| keep(in_set(&a, v), in_set(&b, v)))
+                .collect::<RangeSet>();
+            assert_eq!(op(&a, &b), expected, "{:?} and {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn coalesces_touching_ranges() {
+        let set = [5..8, 0..3, 3..4, 7..10, 20..20]
+            .into_iter()
+            .collect::<RangeSet>();
+        assert_eq!(set.ranges(), &[0..4, 5..10]);
+        assert_eq!(set.len(), 9);
+        assert_eq!(set.min(), Some(0));
+    }
+
+    #[test]
+    fn union_matches_points() {
+        check_against_points(RangeSet::union, |a, b| a || b);
+    }
+
+    #[test]
+    fn intersection_matches_points() {
+        check_against_points(RangeSet::intersection, |a, b| a && b);
+    }
+
+    #[test]
+    fn difference_matches_points() {
+        check_against_points(RangeSet::difference, |a, b| a && !b);
+    }
+
+    #[test]
+    fn contains_matches_points() {
+        let mut rng = Rng::new();
+        for _ in 0..CASES {
+            let set = random_set(&mut rng);
+            for value in 0..DOMAIN {
+                assert_eq!(set.contains(value), in_set(&set, value), "{:?}", set);
+            }
+        }
+    }
+
+    #[test]
+    fn insert_matches_collecting() {
+        let mut rng = Rng::new();
+        for _ in 0..CASES {
+            let ranges = (0..rng.next(6))
+                .map(|_| rng.range(DOMAIN))
+                .collect::<Vec<Range<u64>>>();
+            let mut set = RangeSet::new();
+            for range in &ranges {
+                set.insert(range.clone());
+            }
+            assert_eq!(set, ranges.into_iter().collect::<RangeSet>());
+        }
+    }
+}
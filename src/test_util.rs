@@ -0,0 +1,38 @@
+//! random inputs for the brute-force tests
+
+use std::ops::Range;
+
+/// small xorshift generator, good enough for generating random cases to check against
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new() -> Rng {
+        Rng(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn next(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+
+    /// a possibly empty range within `0..bound`
+    pub fn range(&mut self, bound: u64) -> Range<u64> {
+        let a = self.next(bound);
+        let b = self.next(bound);
+        a.min(b)..a.max(b)
+    }
+
+    /// non-overlapping source ranges in 0..100, with destinations anywhere in 0..100
+    pub fn segments(&mut self) -> Vec<(Range<u64>, u64)> {
+        let mut segments = vec![];
+        let mut start = self.next(10);
+        for _ in 0..self.next(6) {
+            let len = self.next(20);
+            segments.push((start..start + len, self.next(100)));
+            start += len + self.next(10);
+        }
+        segments
+    }
+}